@@ -0,0 +1,300 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::counters::{collect_counter_series, numeric_args, CounterSeries};
+use crate::decode_steps::{percentile, DecodeStep};
+use crate::framework::TimeUnit;
+
+/// Per-step load attribute read from event args or counter tracks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchField {
+    BatchSize,
+    RunningRequests,
+    Tokens,
+}
+
+impl BatchField {
+    pub const ALL: [BatchField; 3] = [
+        BatchField::BatchSize,
+        BatchField::RunningRequests,
+        BatchField::Tokens,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Recognize an args key or counter name, ignoring case and `-`/`.`/space separators.
+    fn from_key(key: &str) -> Option<Self> {
+        let key = key.trim().to_lowercase().replace([' ', '-', '.'], "_");
+        match key.as_str() {
+            "batch_size" | "bs" | "batch" | "num_seqs" | "num_reqs" | "num_requests" => {
+                Some(BatchField::BatchSize)
+            }
+            "num_running_reqs"
+            | "num_running_requests"
+            | "running_requests"
+            | "num_running"
+            | "running" => Some(BatchField::RunningRequests),
+            "num_tokens"
+            | "num_batched_tokens"
+            | "num_scheduled_tokens"
+            | "total_tokens"
+            | "tokens"
+            | "num_input_tokens" => Some(BatchField::Tokens),
+            _ => None,
+        }
+    }
+}
+
+/// A decode step annotated with its load.
+#[derive(Debug, Serialize)]
+pub struct StepBatch {
+    pub step_index: usize,
+    pub start_time_us: f64,
+    pub latency_ms: f64,
+    pub batch_size: Option<f64>,
+    pub running_requests: Option<f64>,
+    pub num_tokens: Option<f64>,
+    /// Latency per token; empty without a token count.
+    pub per_token_ms: Option<f64>,
+    /// Latency per sequence in the batch; empty without a batch size.
+    pub per_seq_ms: Option<f64>,
+}
+
+/// Recognized args of one non-counter event.
+struct ArgSample {
+    time: f64,
+    pid: Option<Value>,
+    values: [Option<f64>; 3],
+}
+
+/// Counter series carrying one of the fields.
+struct FieldSeries {
+    field: BatchField,
+    pid: String,
+    series: CounterSeries,
+}
+
+fn pid_string(pid: &Option<Value>) -> String {
+    match pid {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Events whose args carry any of the fields, sorted by time (us).
+fn collect_arg_samples(trace_events: &[Value], units: TimeUnit) -> Vec<ArgSample> {
+    let mut samples: Vec<ArgSample> = Vec::new();
+    for event in trace_events {
+        if event["ph"].as_str() == Some("C") {
+            continue;
+        }
+        let (Some(ts), Some(args)) = (event["ts"].as_f64(), event["args"].as_object()) else {
+            continue;
+        };
+        let mut values = [None; 3];
+        for (key, value) in numeric_args(args) {
+            if let Some(field) = BatchField::from_key(key) {
+                values[field.index()].get_or_insert(value);
+            }
+        }
+        if values.iter().any(Option::is_some) {
+            samples.push(ArgSample {
+                time: units.to_us(ts),
+                pid: event.get("pid").cloned(),
+                values,
+            });
+        }
+    }
+    samples.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+    samples
+}
+
+/// Counter series named after a field, either by args key or by event name.
+fn collect_field_series(trace_events: &[Value], units: TimeUnit) -> Vec<FieldSeries> {
+    collect_counter_series(trace_events)
+        .into_iter()
+        .filter_map(|(key, mut series)| {
+            let (event, arg) = key.name.rsplit_once('.')?;
+            let field = BatchField::from_key(arg).or_else(|| BatchField::from_key(event))?;
+            for sample in series.samples.iter_mut() {
+                sample.0 = units.to_us(sample.0);
+            }
+            Some(FieldSeries {
+                field,
+                pid: key.pid,
+                series,
+            })
+        })
+        .collect()
+}
+
+/// Annotate each step with batch size, running requests and token counts. Args of events
+/// inside the step (the marker's process first) take precedence over counters, which use
+/// their first sample inside the step or else the value in effect at its start.
+pub fn annotate_steps(
+    trace_events: &[Value],
+    steps: &[DecodeStep],
+    units: TimeUnit,
+) -> Vec<StepBatch> {
+    let samples = collect_arg_samples(trace_events, units);
+    let counters = collect_field_series(trace_events, units);
+
+    steps
+        .iter()
+        .map(|step| {
+            let first = samples.partition_point(|s| s.time < step.start_time);
+            let last = samples.partition_point(|s| s.time < step.end_time());
+            let inside = &samples[first..last];
+            let step_pid = pid_string(&step.pid);
+
+            let mut values = [None; 3];
+            for field in BatchField::ALL {
+                let idx = field.index();
+                let from_args = inside
+                    .iter()
+                    .filter(|s| s.values[idx].is_some())
+                    .find(|s| s.pid == step.pid)
+                    .or_else(|| inside.iter().find(|s| s.values[idx].is_some()))
+                    .and_then(|s| s.values[idx]);
+                let mut field_counters = counters.iter().filter(|c| c.field == field);
+                let from_counters = field_counters
+                    .clone()
+                    .find(|c| c.pid == step_pid)
+                    .or_else(|| field_counters.next())
+                    .and_then(|c| c.series.value_in(step.start_time, step.end_time()));
+                values[idx] = from_args.or(from_counters);
+            }
+
+            let [batch_size, running_requests, num_tokens] = values;
+            let per_unit = |n: Option<f64>| n.filter(|&n| n > 0.0).map(|n| step.latency_ms / n);
+            StepBatch {
+                step_index: step.index,
+                start_time_us: step.start_time,
+                latency_ms: step.latency_ms,
+                batch_size,
+                running_requests,
+                num_tokens,
+                per_token_ms: per_unit(num_tokens),
+                per_seq_ms: per_unit(batch_size),
+            }
+        })
+        .collect()
+}
+
+/// Print latency bucketed by batch size; buckets are `bucket_width` sequences wide.
+pub fn print_batch_report(annotated: &[StepBatch], bucket_width: usize) {
+    let found =
+        |f: fn(&StepBatch) -> Option<f64>| annotated.iter().filter(|s| f(s).is_some()).count();
+    println!("\n--- Latency by batch size ---");
+    println!(
+        "Annotated {} steps: batch size {}, running requests {}, tokens {}",
+        annotated.len(),
+        found(|s| s.batch_size),
+        found(|s| s.running_requests),
+        found(|s| s.num_tokens)
+    );
+
+    let print_mean = |label: &str, f: fn(&StepBatch) -> Option<f64>| {
+        let values: Vec<f64> = annotated.iter().filter_map(f).collect();
+        if !values.is_empty() {
+            println!(
+                "Mean {} latency: {:.4} ms over {} steps",
+                label,
+                values.iter().sum::<f64>() / values.len() as f64,
+                values.len()
+            );
+        }
+    };
+    print_mean("per-token", |s| s.per_token_ms);
+    print_mean("per-sequence", |s| s.per_seq_ms);
+
+    let width = bucket_width.max(1);
+    let mut buckets: BTreeMap<usize, Vec<&StepBatch>> = BTreeMap::new();
+    for step in annotated {
+        if let Some(batch) = step.batch_size {
+            let lo = batch.max(0.0) as usize / width * width;
+            buckets.entry(lo).or_default().push(step);
+        }
+    }
+    if buckets.is_empty() {
+        println!("No batch size found in event args or counter tracks");
+        println!("  (recognized keys: batch_size, bs, batch, num_seqs, num_reqs, num_requests)");
+        return;
+    }
+
+    println!(
+        "{:>10} {:>8} {:>12} {:>12} {:>12} {:>12} {:>12} {:>16} {:>14}",
+        "Batch",
+        "Steps",
+        "Mean (ms)",
+        "P50 (ms)",
+        "P90 (ms)",
+        "P99 (ms)",
+        "Tokens",
+        "Per-token (ms)",
+        "Per-seq (ms)"
+    );
+    for (lo, steps) in &buckets {
+        let mut latencies: Vec<f64> = steps.iter().map(|s| s.latency_ms).collect();
+        latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mean = latencies.iter().sum::<f64>() / latencies.len() as f64;
+        let tokens: Vec<f64> = steps.iter().filter_map(|s| s.num_tokens).collect();
+        let per_token: Vec<f64> = steps.iter().filter_map(|s| s.per_token_ms).collect();
+        let per_seq: Vec<f64> = steps.iter().filter_map(|s| s.per_seq_ms).collect();
+        let average = |values: &[f64], precision: usize| {
+            if values.is_empty() {
+                "-".to_string()
+            } else {
+                format!(
+                    "{:.*}",
+                    precision,
+                    values.iter().sum::<f64>() / values.len() as f64
+                )
+            }
+        };
+        let label = if width == 1 {
+            lo.to_string()
+        } else {
+            format!("{}-{}", lo, lo + width - 1)
+        };
+        println!(
+            "{:>10} {:>8} {:>12.3} {:>12.3} {:>12.3} {:>12.3} {:>12} {:>16} {:>14}",
+            label,
+            steps.len(),
+            mean,
+            percentile(&latencies, 50.0),
+            percentile(&latencies, 90.0),
+            percentile(&latencies, 99.0),
+            average(&tokens, 1),
+            average(&per_token, 4),
+            average(&per_seq, 4)
+        );
+    }
+}
+
+/// Write the per-step annotations to a CSV file.
+pub fn write_batch_csv(annotated: &[StepBatch], output_file: &str) -> Result<(), Box<dyn Error>> {
+    println!("\nWriting step annotations to CSV: {}", output_file);
+    let csv_file = File::create(output_file)?;
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(csv_file));
+
+    for record in annotated {
+        wtr.serialize(record)?;
+    }
+
+    wtr.flush()?;
+    println!(
+        "Successfully wrote {} records to {}",
+        annotated.len(),
+        output_file
+    );
+
+    Ok(())
+}
@@ -0,0 +1,486 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::common::load_trace_json;
+use crate::decode_steps::{compute_statistics, extract_decode_steps, DecodeStats};
+use crate::framework::FrameworkProfile;
+
+/// Tolerance key applied to metrics without their own entry.
+const DEFAULT_TOLERANCE_KEY: &str = "default";
+/// Tolerance key applied to every kernel.
+const KERNEL_TOLERANCE_KEY: &str = "kernels";
+/// Metric name prefix of kernel rows.
+const KERNEL_METRIC_PREFIX: &str = "kernel:";
+
+/// Regression gate configuration.
+pub struct CheckConfig {
+    pub framework: FrameworkProfile,
+    pub input_file: String,
+    pub baseline_file: String,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub auto_filter: bool,
+    /// Output CSV of the `stats` command; its per-kernel averages are checked too.
+    pub stats_csv: Option<String>,
+    /// Write the measured values into the baseline file instead of checking.
+    pub update_baseline: bool,
+}
+
+/// Stored baseline. Tolerances are the allowed increase in percent.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Baseline {
+    /// Decode step statistics (ms) by name, see `DecodeStats::metrics`.
+    #[serde(default)]
+    pub decode: BTreeMap<String, f64>,
+    /// Average time per step (us) by kernel name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub kernels: BTreeMap<String, f64>,
+    /// Per-metric tolerances, plus `default` and `kernels`.
+    #[serde(default)]
+    pub tolerances: BTreeMap<String, f64>,
+}
+
+/// Outcome of one metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// No tolerance configured; reported only.
+    Unchecked,
+    /// In the baseline but not measured.
+    Missing,
+    /// Measured but not in the baseline.
+    New,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Fail => "FAIL",
+            CheckStatus::Unchecked => "-",
+            CheckStatus::Missing => "MISSING",
+            CheckStatus::New => "NEW",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// One row of the pass/fail table.
+pub struct CheckRow {
+    pub metric: String,
+    pub unit: &'static str,
+    pub baseline: Option<f64>,
+    pub current: Option<f64>,
+    pub tolerance_pct: Option<f64>,
+    pub status: CheckStatus,
+}
+
+impl CheckRow {
+    /// Whether the row fails the gate: a regression, or a decode metric in the baseline
+    /// that was not measured. Missing kernels are reported only.
+    fn is_failure(&self) -> bool {
+        match self.status {
+            CheckStatus::Fail => true,
+            CheckStatus::Missing => !self.metric.starts_with(KERNEL_METRIC_PREFIX),
+            _ => false,
+        }
+    }
+
+    fn change_pct(&self) -> Option<f64> {
+        match (self.baseline, self.current) {
+            (Some(b), Some(c)) if b != 0.0 => Some((c - b) / b * 100.0),
+            _ => None,
+        }
+    }
+}
+
+/// Per-step time of each kernel from a `stats` CSV; kernels appearing several times in
+/// a step are summed.
+#[derive(Debug, Deserialize)]
+struct StatsCsvRow {
+    operation_name: String,
+    avg_duration_us: f64,
+}
+
+fn load_kernel_averages(path: &str) -> Result<BTreeMap<String, f64>, Box<dyn Error>> {
+    let mut rdr = csv::Reader::from_path(path)?;
+    let mut kernels: BTreeMap<String, f64> = BTreeMap::new();
+    for row in rdr.deserialize() {
+        let row: StatsCsvRow = row?;
+        *kernels.entry(row.operation_name).or_insert(0.0) += row.avg_duration_us;
+    }
+    println!("Loaded {} kernels from {}", kernels.len(), path);
+    Ok(kernels)
+}
+
+/// Result of a check: one row per metric and the window the statistics came from.
+pub struct CheckResult {
+    pub rows: Vec<CheckRow>,
+    pub window: String,
+}
+
+impl CheckResult {
+    /// Number of metrics compared against a tolerance.
+    fn checked(&self) -> usize {
+        self.rows
+            .iter()
+            .filter(|r| matches!(r.status, CheckStatus::Pass | CheckStatus::Fail))
+            .count()
+    }
+
+    fn failures(&self) -> Vec<&CheckRow> {
+        self.rows.iter().filter(|r| r.is_failure()).collect()
+    }
+
+    /// The gate passes only if something was checked and nothing failed.
+    pub fn passed(&self) -> bool {
+        self.checked() > 0 && self.failures().is_empty()
+    }
+}
+
+impl Baseline {
+    /// Load a baseline file and validate its metric and tolerance names.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let baseline: Baseline =
+            serde_json::from_str(&text).map_err(|e| format!("Invalid baseline {}: {}", path, e))?;
+
+        let metric_names: Vec<&str> = DecodeStats::default().metrics().map(|(n, _)| n).to_vec();
+        for name in baseline.decode.keys() {
+            if name != "count" && !metric_names.contains(&name.as_str()) {
+                return Err(format!(
+                    "Invalid baseline {}: unknown decode metric '{}'. Supported: count, {}",
+                    path,
+                    name,
+                    metric_names.join(", ")
+                )
+                .into());
+            }
+        }
+        for (name, &tolerance) in &baseline.tolerances {
+            let known = name == DEFAULT_TOLERANCE_KEY
+                || name == KERNEL_TOLERANCE_KEY
+                || metric_names.contains(&name.as_str());
+            if !known {
+                return Err(format!(
+                    "Invalid baseline {}: unknown tolerance '{}' (use a decode metric, '{}' or '{}')",
+                    path, name, DEFAULT_TOLERANCE_KEY, KERNEL_TOLERANCE_KEY
+                )
+                .into());
+            }
+            if tolerance < 0.0 {
+                return Err(format!(
+                    "Invalid baseline {}: tolerance '{}' must be non-negative",
+                    path, name
+                )
+                .into());
+            }
+        }
+        Ok(baseline)
+    }
+
+    fn tolerance(&self, metric: &str) -> Option<f64> {
+        self.tolerances
+            .get(metric)
+            .or_else(|| self.tolerances.get(DEFAULT_TOLERANCE_KEY))
+            .copied()
+    }
+}
+
+/// Compare one metric against its baseline; only increases count as regressions.
+fn check_metric(
+    metric: String,
+    unit: &'static str,
+    baseline: Option<f64>,
+    current: Option<f64>,
+    tolerance_pct: Option<f64>,
+) -> CheckRow {
+    let status = match (baseline, current, tolerance_pct) {
+        (Some(_), None, _) => CheckStatus::Missing,
+        (None, Some(_), _) => CheckStatus::New,
+        (Some(b), Some(c), Some(tol)) => {
+            if c > b * (1.0 + tol / 100.0) {
+                CheckStatus::Fail
+            } else {
+                CheckStatus::Pass
+            }
+        }
+        _ => CheckStatus::Unchecked,
+    };
+    CheckRow {
+        metric,
+        unit,
+        baseline,
+        current,
+        tolerance_pct,
+        status,
+    }
+}
+
+/// Rows of the decode metrics in the baseline. Without statistics (no decode steps were
+/// found), every baselined metric is `Missing`.
+fn decode_rows(baseline: &Baseline, stats: Option<&DecodeStats>) -> Vec<CheckRow> {
+    let mut rows: Vec<CheckRow> = Vec::new();
+    if let Some(&count) = baseline.decode.get("count") {
+        rows.push(check_metric(
+            "count".to_string(),
+            "",
+            Some(count),
+            stats.map(|s| s.count as f64),
+            None,
+        ));
+    }
+    let measured = stats.map(DecodeStats::metrics);
+    for (idx, (name, _)) in DecodeStats::default().metrics().into_iter().enumerate() {
+        if let Some(&expected) = baseline.decode.get(name) {
+            rows.push(check_metric(
+                name.to_string(),
+                "ms",
+                Some(expected),
+                measured.map(|m| m[idx].1),
+                baseline.tolerance(name),
+            ));
+        }
+    }
+    rows
+}
+
+/// Measure the trace and compare it against the baseline (or rewrite the baseline).
+pub fn run_check(config: &CheckConfig) -> Result<CheckResult, Box<dyn Error>> {
+    let baseline = if Path::new(&config.baseline_file).is_file() || !config.update_baseline {
+        Baseline::load(&config.baseline_file)?
+    } else {
+        Baseline::default()
+    };
+
+    let json = load_trace_json(&config.input_file)?;
+    let trace_events = json["traceEvents"]
+        .as_array()
+        .ok_or("traceEvents not found or not an array")?;
+    let selection = extract_decode_steps(
+        trace_events,
+        &config.framework,
+        (config.min_ms, config.max_ms),
+        config.auto_filter,
+    );
+    let latencies: Vec<f64> = selection.steps.iter().map(|step| step.latency_ms).collect();
+    let stats = compute_statistics(&latencies);
+    let window = selection.describe();
+    let kernels = match config.stats_csv {
+        Some(ref path) => load_kernel_averages(path)?,
+        None => BTreeMap::new(),
+    };
+
+    if config.update_baseline {
+        let stats = stats.ok_or_else(|| format!("No {} decode steps found", config.framework))?;
+        let mut decode: BTreeMap<String, f64> = stats
+            .metrics()
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value))
+            .collect();
+        decode.insert("count".to_string(), stats.count as f64);
+        let updated = Baseline {
+            decode,
+            kernels,
+            tolerances: baseline.tolerances,
+        };
+        fs::write(
+            &config.baseline_file,
+            serde_json::to_string_pretty(&updated)? + "\n",
+        )?;
+        println!("\nWrote baseline to {}", config.baseline_file);
+        println!("Window: {}", window);
+        if updated.tolerances.is_empty() {
+            println!(
+                "Note: no tolerances set; add e.g. \"tolerances\": {{\"p99\": 5, \"mean\": 3}}"
+            );
+        }
+        return Ok(CheckResult {
+            rows: Vec::new(),
+            window,
+        });
+    }
+
+    if stats.is_none() {
+        println!("Warning: no {} decode steps found", config.framework);
+    }
+    let mut rows = decode_rows(&baseline, stats.as_ref());
+
+    if config.stats_csv.is_some() {
+        let tolerance = baseline.tolerances.get(KERNEL_TOLERANCE_KEY).copied();
+        let names: BTreeSet<&String> = baseline.kernels.keys().chain(kernels.keys()).collect();
+        for name in names {
+            rows.push(check_metric(
+                format!("{}{}", KERNEL_METRIC_PREFIX, name),
+                "us",
+                baseline.kernels.get(name).copied(),
+                kernels.get(name).copied(),
+                tolerance,
+            ));
+        }
+    } else if !baseline.kernels.is_empty() {
+        println!(
+            "Note: baseline has {} kernels; pass --stats-csv to check them",
+            baseline.kernels.len()
+        );
+    }
+
+    Ok(CheckResult { rows, window })
+}
+
+/// Print the pass/fail table; returns whether the gate passed.
+pub fn print_check_table(result: &CheckResult) -> bool {
+    let rows = &result.rows;
+    let width = rows
+        .iter()
+        .map(|r| r.metric.chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(10, 60);
+    let fmt_value = |value: Option<f64>, unit: &str| {
+        value.map_or("-".to_string(), |v| format!("{:.3} {}", v, unit))
+    };
+
+    println!("\n{}", "=".repeat(width + 66));
+    println!("Regression Check");
+    println!("Window: {}", result.window);
+    println!("{}", "=".repeat(width + 66));
+    println!(
+        "{:<width$} {:>16} {:>16} {:>10} {:>10} {:>8}",
+        "Metric",
+        "Baseline",
+        "Current",
+        "Change",
+        "Tolerance",
+        "Status",
+        width = width
+    );
+    for row in rows {
+        let metric = if row.metric.chars().count() > width {
+            let tail: String = row.metric.chars().rev().take(width - 3).collect();
+            format!("...{}", tail.chars().rev().collect::<String>())
+        } else {
+            row.metric.clone()
+        };
+        println!(
+            "{:<width$} {:>16} {:>16} {:>10} {:>10} {:>8}",
+            metric,
+            fmt_value(row.baseline, row.unit),
+            fmt_value(row.current, row.unit),
+            row.change_pct()
+                .map_or("-".to_string(), |c| format!("{:+.2}%", c)),
+            row.tolerance_pct
+                .map_or("-".to_string(), |t| format!("+{}%", t)),
+            row.status,
+            width = width
+        );
+    }
+
+    let failed = result.failures();
+    let regressions = failed
+        .iter()
+        .filter(|r| r.status == CheckStatus::Fail)
+        .count();
+    println!(
+        "\n{} metrics checked, {} regressions",
+        result.checked(),
+        regressions
+    );
+    if !failed.is_empty() {
+        let names: Vec<&str> = failed.iter().map(|r| r.metric.as_str()).collect();
+        println!("RESULT: FAIL ({})", names.join(", "));
+    } else if result.checked() == 0 {
+        println!("RESULT: FAIL (no metrics checked)");
+    } else {
+        println!("RESULT: PASS");
+    }
+    result.passed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn baseline() -> Baseline {
+        Baseline {
+            decode: BTreeMap::from([
+                ("count".to_string(), 200.0),
+                ("mean".to_string(), 15.0),
+                ("p99".to_string(), 16.5),
+            ]),
+            kernels: BTreeMap::new(),
+            tolerances: BTreeMap::from([("p99".to_string(), 5.0), ("default".to_string(), 3.0)]),
+        }
+    }
+
+    fn result(rows: Vec<CheckRow>) -> CheckResult {
+        CheckResult {
+            rows,
+            window: String::new(),
+        }
+    }
+
+    #[test]
+    fn only_increases_beyond_tolerance_fail() {
+        let status =
+            |current| check_metric("p99".into(), "ms", Some(10.0), current, Some(5.0)).status;
+        assert_eq!(status(Some(10.5)), CheckStatus::Pass);
+        assert_eq!(status(Some(10.6)), CheckStatus::Fail);
+        assert_eq!(status(Some(8.0)), CheckStatus::Pass);
+        assert_eq!(status(None), CheckStatus::Missing);
+        assert_eq!(
+            check_metric("p99".into(), "ms", None, Some(1.0), Some(5.0)).status,
+            CheckStatus::New
+        );
+        assert_eq!(
+            check_metric("p99".into(), "ms", Some(10.0), Some(20.0), None).status,
+            CheckStatus::Unchecked
+        );
+    }
+
+    #[test]
+    fn measured_decode_metrics_pass() {
+        let stats = DecodeStats {
+            count: 190,
+            mean: 15.2,
+            p99: 16.0,
+            ..DecodeStats::default()
+        };
+        let rows = decode_rows(&baseline(), Some(&stats));
+        let metrics: Vec<&str> = rows.iter().map(|r| r.metric.as_str()).collect();
+        assert_eq!(metrics, ["count", "mean", "p99"]);
+        assert!(result(rows).passed());
+    }
+
+    #[test]
+    fn no_decode_steps_fails() {
+        let rows = decode_rows(&baseline(), None);
+        assert!(rows.iter().all(|r| r.status == CheckStatus::Missing));
+        let result = result(rows);
+        assert_eq!(result.checked(), 0);
+        assert_eq!(result.failures().len(), 3);
+        assert!(!result.passed());
+    }
+
+    #[test]
+    fn nothing_checked_fails() {
+        assert!(!result(Vec::new()).passed());
+        let unchecked = check_metric("mean".into(), "ms", Some(15.0), Some(15.0), None);
+        assert!(!result(vec![unchecked]).passed());
+    }
+
+    #[test]
+    fn missing_kernel_is_reported_only() {
+        let rows = vec![
+            check_metric("p99".into(), "ms", Some(16.5), Some(16.0), Some(5.0)),
+            check_metric("kernel:gemm".into(), "us", Some(2900.0), None, Some(10.0)),
+        ];
+        assert!(result(rows).passed());
+    }
+}
@@ -1,5 +1,6 @@
 use serde::Deserialize;
-use serde_json::Value;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
 use std::io::BufReader;
@@ -17,9 +18,87 @@ pub struct TraceEvent {
     #[serde(default)]
     pub dur: Option<f64>,
     #[serde(default)]
+    pub pid: Option<Value>,
+    #[serde(default)]
+    pub tid: Option<Value>,
+    #[serde(default)]
     pub args: Option<TraceArgs>,
 }
 
+impl TraceEvent {
+    /// Start and end time (us) of a complete event.
+    /// Prefers Paddle's `args.start_time`/`args.end_time` and falls back to `ts`/`dur`.
+    pub fn time_range(&self) -> Option<(f64, f64)> {
+        if let Some(args) = &self.args {
+            if let (Some(start_str), Some(end_str)) = (&args.start_time, &args.end_time) {
+                if let (Some(start), Some(end)) = (
+                    parse_time_from_string(start_str),
+                    parse_time_from_string(end_str),
+                ) {
+                    return Some((start, end));
+                }
+            }
+        }
+        match (self.ts, self.dur) {
+            (Some(ts), Some(dur)) => Some((ts, ts + dur)),
+            _ => None,
+        }
+    }
+
+    /// Device id from `args.device`, falling back to a numeric `pid`.
+    pub fn device(&self) -> Option<i64> {
+        self.args
+            .as_ref()
+            .and_then(|args| args.device.as_ref())
+            .or(self.pid.as_ref())
+            .and_then(value_as_i64)
+    }
+
+    /// Stream id from `args.stream`, falling back to a numeric `tid`.
+    pub fn stream(&self) -> Option<i64> {
+        self.args
+            .as_ref()
+            .and_then(|args| args.stream.as_ref())
+            .or(self.tid.as_ref())
+            .and_then(value_as_i64)
+    }
+
+    /// Stream waited on, for cross-stream synchronization events.
+    pub fn wait_on_stream(&self) -> Option<i64> {
+        self.args
+            .as_ref()
+            .and_then(|args| args.wait_on_stream.as_ref())
+            .and_then(value_as_i64)
+    }
+}
+
+/// Interpret a JSON number or numeric string as an integer.
+pub fn value_as_i64(value: &Value) -> Option<i64> {
+    value
+        .as_i64()
+        .or_else(|| value.as_str().and_then(|s| s.trim().parse().ok()))
+}
+
+/// Kind of GPU operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuOpKind {
+    Kernel,
+    Memcpy,
+    Memset,
+}
+
+impl GpuOpKind {
+    /// Map an event category to a GPU operation kind (Paddle or Kineto naming).
+    pub fn from_category(cat: &str) -> Option<Self> {
+        match cat {
+            "Kernel" | "kernel" => Some(GpuOpKind::Kernel),
+            "Memcpy" | "gpu_memcpy" => Some(GpuOpKind::Memcpy),
+            "Memset" | "gpu_memset" => Some(GpuOpKind::Memset),
+            _ => None,
+        }
+    }
+}
+
 /// Event arguments.
 #[derive(Debug, Deserialize)]
 pub struct TraceArgs {
@@ -27,6 +106,100 @@ pub struct TraceArgs {
     pub start_time: Option<String>,
     #[serde(default)]
     pub end_time: Option<String>,
+    #[serde(default)]
+    pub device: Option<Value>,
+    #[serde(default)]
+    pub stream: Option<Value>,
+    /// Stream waited on by a Kineto `cuda_sync` event.
+    #[serde(default)]
+    pub wait_on_stream: Option<Value>,
+    /// Kernel launch configuration (Kineto / Paddle kernel events).
+    #[serde(default)]
+    pub grid: Option<Value>,
+    #[serde(default)]
+    pub block: Option<Value>,
+    #[serde(default, rename = "registers per thread")]
+    pub registers_per_thread: Option<Value>,
+    #[serde(default, rename = "shared memory")]
+    pub shared_memory: Option<Value>,
+    #[serde(
+        default,
+        rename = "est. achieved occupancy %",
+        alias = "theoretical achieved occupancy %"
+    )]
+    pub achieved_occupancy: Option<Value>,
+    /// Transfer size of memcpy / memset events.
+    #[serde(default, alias = "num_bytes")]
+    pub bytes: Option<Value>,
+    /// Links GPU activity to the runtime call that launched it.
+    #[serde(default, alias = "correlation id")]
+    pub correlation: Option<Value>,
+}
+
+/// Normalize operation name by stripping the trailing dynamic duration suffix.
+/// e.g. "MEMCPY_DtoH[2.464 us]" -> "MEMCPY_DtoH"
+/// e.g. "kernel_name[123.456 us]" -> "kernel_name"
+pub fn normalize_op_name(name: &str) -> &str {
+    // Find the last '[' and check if it is a duration suffix.
+    if let Some(bracket_pos) = name.rfind('[') {
+        let suffix = &name[bracket_pos..];
+        // Match "[<number> us]" or "[<number> ms]" format.
+        if suffix.ends_with(" us]") || suffix.ends_with(" ms]") {
+            return &name[..bracket_pos];
+        }
+    }
+    name
+}
+
+/// GPU operation record.
+#[derive(Debug, Clone)]
+pub struct GpuOperation {
+    pub name: String,
+    pub start_time: f64,
+    pub end_time: f64,
+    pub duration: f64,
+    pub kind: GpuOpKind,
+    pub device: Option<i64>,
+    pub stream: Option<i64>,
+}
+
+impl GpuOperation {
+    /// Build a GPU operation from a trace event; `None` if the event is not a GPU operation.
+    pub fn from_event(event: &TraceEvent, name: String, start: f64, end: f64) -> Option<Self> {
+        let kind = GpuOpKind::from_category(event.cat.as_deref()?)?;
+        Some(Self {
+            name,
+            start_time: start,
+            end_time: end,
+            duration: end - start,
+            kind,
+            device: event.device(),
+            stream: event.stream(),
+        })
+    }
+
+    /// Copy of the operation with timestamps relative to `base`.
+    pub fn rebased(&self, base: f64) -> Self {
+        Self {
+            name: self.name.clone(),
+            start_time: self.start_time - base,
+            end_time: self.end_time - base,
+            ..*self
+        }
+    }
+}
+
+/// GPU operations fully contained in `[start, end]`.
+/// `ops` must be sorted by start time; the window is located by binary search,
+/// so assigning operations to many sorted windows is linear in the trace size.
+pub fn operations_within(
+    ops: &[GpuOperation],
+    start: f64,
+    end: f64,
+) -> impl Iterator<Item = &GpuOperation> {
+    let first = ops.partition_point(|op| op.start_time < start);
+    let last = first + ops[first..].partition_point(|op| op.start_time <= end);
+    ops[first..last].iter().filter(move |op| op.end_time <= end)
 }
 
 /// Parse a time string, e.g. "6609483.000 us".
@@ -46,7 +219,7 @@ pub fn load_trace_json(input_file: &str) -> Result<Value, Box<dyn Error>> {
     let reader = BufReader::new(file);
 
     println!("Parsing JSON (this may take a while for large files)...");
-    let json: Value = serde_json::from_reader(reader)?;
+    let mut json: Value = serde_json::from_reader(reader)?;
 
     let count = json["traceEvents"]
         .as_array()
@@ -54,5 +227,126 @@ pub fn load_trace_json(input_file: &str) -> Result<Value, Box<dyn Error>> {
         .len();
 
     println!("Total events in file: {}", count);
+
+    if let Some(events) = json["traceEvents"].as_array_mut() {
+        normalize_duration_events(events);
+    }
     Ok(json)
 }
+
+/// Key of an async span: category, scope and id. Ids under `id2.local` are scoped to the process.
+fn async_span_key(event: &Value) -> Option<String> {
+    let cat = event["cat"].as_str().unwrap_or("");
+    let scope = event["scope"].as_str().unwrap_or("");
+    let id = if !event["id"].is_null() {
+        event["id"].to_string()
+    } else if !event["id2"]["global"].is_null() {
+        event["id2"]["global"].to_string()
+    } else if !event["id2"]["local"].is_null() {
+        format!("{}@{}", event["id2"]["local"], event["pid"])
+    } else {
+        return None;
+    };
+    Some(format!("{}\u{1}{}\u{1}{}", cat, scope, id))
+}
+
+/// Build a complete (`X`) event from a begin event and its matching end event.
+/// Arguments of the end event are merged into those of the begin event.
+fn complete_span(begin: &Value, end: &Value) -> Option<Value> {
+    let start = begin["ts"].as_f64()?;
+    let finish = end["ts"].as_f64()?;
+    if finish < start {
+        return None;
+    }
+
+    let mut span = begin.as_object()?.clone();
+    span.insert("ph".to_string(), Value::from("X"));
+    span.insert("dur".to_string(), Value::from(finish - start));
+    if let Some(end_args) = end["args"].as_object() {
+        let args = span
+            .entry("args")
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Some(args) = args.as_object_mut() {
+            for (key, value) in end_args {
+                args.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+    }
+    Some(Value::Object(span))
+}
+
+/// Match `B`/`E` duration events (per-thread stacks) and async `b`/`e` spans (keyed by
+/// category and id) and append them as complete `X` events, so that every analysis that
+/// reads complete events also sees them. The original events are left in place.
+pub fn normalize_duration_events(events: &mut Vec<Value>) {
+    // Stable sort by timestamp so out-of-order files still pair correctly.
+    let mut order: Vec<usize> = events
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| matches!(e["ph"].as_str(), Some("B" | "E" | "b" | "e")))
+        .filter(|(_, e)| e["ts"].as_f64().is_some())
+        .map(|(idx, _)| idx)
+        .collect();
+    if order.is_empty() {
+        return;
+    }
+    order.sort_by(|&a, &b| {
+        events[a]["ts"]
+            .as_f64()
+            .partial_cmp(&events[b]["ts"].as_f64())
+            .unwrap()
+    });
+
+    let mut thread_stacks: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut async_stacks: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut spans: Vec<Value> = Vec::new();
+    let (mut sync_count, mut async_count) = (0, 0);
+
+    for idx in order {
+        let event = &events[idx];
+        match event["ph"].as_str() {
+            Some("B") => {
+                let thread = format!("{}/{}", event["pid"], event["tid"]);
+                thread_stacks.entry(thread).or_default().push(idx);
+            }
+            Some("E") => {
+                let thread = format!("{}/{}", event["pid"], event["tid"]);
+                let begin = thread_stacks.get_mut(&thread).and_then(|stack| stack.pop());
+                if let Some(span) = begin.and_then(|b| complete_span(&events[b], event)) {
+                    spans.push(span);
+                    sync_count += 1;
+                }
+            }
+            Some("b") => {
+                if let Some(key) = async_span_key(event) {
+                    async_stacks.entry(key).or_default().push(idx);
+                }
+            }
+            Some("e") => {
+                let Some(stack) = async_span_key(event).and_then(|key| async_stacks.get_mut(&key))
+                else {
+                    continue;
+                };
+                // Nested async spans share an id; close the innermost one with the same name.
+                let position = stack
+                    .iter()
+                    .rposition(|&b| events[b]["name"] == event["name"])
+                    .or(stack.len().checked_sub(1));
+                let begin = position.map(|p| stack.remove(p));
+                if let Some(span) = begin.and_then(|b| complete_span(&events[b], event)) {
+                    spans.push(span);
+                    async_count += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if sync_count + async_count > 0 {
+        println!(
+            "Normalized {} B/E spans and {} async b/e spans into complete events",
+            sync_count, async_count
+        );
+    }
+    events.extend(spans);
+}
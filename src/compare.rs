@@ -0,0 +1,467 @@
+use serde::Serialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::common::load_trace_json;
+use crate::decode_steps::{compute_statistics, extract_decode_steps, percentile, DecodeStats};
+use crate::framework::FrameworkProfile;
+
+/// A/B comparison of decode step latency between two traces.
+pub struct CompareConfig {
+    pub baseline_framework: FrameworkProfile,
+    pub baseline_file: String,
+    pub candidate_framework: FrameworkProfile,
+    pub candidate_file: String,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub auto_filter: bool,
+    /// Number of bootstrap resamples for the confidence intervals.
+    pub resamples: usize,
+    /// Confidence level of the intervals; `1 - confidence` is the significance level.
+    pub confidence: f64,
+    /// Seed of the resampling RNG, so reports are reproducible.
+    pub seed: u64,
+    pub output_csv: Option<String>,
+}
+
+/// Difference of one statistic, candidate minus baseline (ms).
+#[derive(Debug, Serialize)]
+pub struct MetricComparison {
+    pub statistic: &'static str,
+    pub baseline: f64,
+    pub candidate: f64,
+    pub delta: f64,
+    pub change_pct: f64,
+    pub ci_low: f64,
+    pub ci_high: f64,
+    /// The confidence interval of the delta excludes zero.
+    pub significant: bool,
+}
+
+/// Two-sided Mann-Whitney U test of baseline versus candidate latencies.
+pub struct MannWhitney {
+    /// U statistic of the candidate sample.
+    pub u: f64,
+    pub z: f64,
+    pub p_value: f64,
+    /// Probability that a random candidate step is slower than a random baseline step
+    /// (ties count half).
+    pub prob_candidate_slower: f64,
+}
+
+/// Result of a comparison.
+pub struct Comparison {
+    pub baseline_count: usize,
+    pub candidate_count: usize,
+    pub metrics: Vec<MetricComparison>,
+    pub mann_whitney: MannWhitney,
+    pub confidence: f64,
+    /// Steady-state window and filter used on each side.
+    pub baseline_window: String,
+    pub candidate_window: String,
+}
+
+/// xorshift64 generator; small, deterministic and good enough for resampling indices.
+struct XorShift64(u64);
+
+impl XorShift64 {
+    /// Seed through splitmix64, so similar seeds give unrelated streams.
+    fn new(seed: u64) -> Self {
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        // The all-zero state is a fixed point; one seed maps to it.
+        Self(if z == 0 { 0x9E37_79B9_7F4A_7C15 } else { z })
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    fn index(&mut self, len: usize) -> usize {
+        (self.next_u64() % len as u64) as usize
+    }
+}
+
+/// Complementary error function (Numerical Recipes `erfcc`, relative error < 1.2e-7).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let ans = t
+        * (-z * z - 1.265_512_23
+            + t * (1.000_023_68
+                + t * (0.374_091_96
+                    + t * (0.096_784_18
+                        + t * (-0.186_288_06
+                            + t * (0.278_868_07
+                                + t * (-1.135_203_98
+                                    + t * (1.488_515_87
+                                        + t * (-0.822_152_23 + t * 0.170_872_77)))))))))
+            .exp();
+    if x >= 0.0 {
+        ans
+    } else {
+        2.0 - ans
+    }
+}
+
+/// Mann-Whitney U test with average ranks for ties, tie-corrected variance and
+/// continuity correction (normal approximation).
+fn mann_whitney(baseline: &[f64], candidate: &[f64]) -> MannWhitney {
+    let n1 = baseline.len() as f64;
+    let n2 = candidate.len() as f64;
+    let n = n1 + n2;
+
+    // (value, from candidate)
+    let mut pooled: Vec<(f64, bool)> = baseline
+        .iter()
+        .map(|&x| (x, false))
+        .chain(candidate.iter().map(|&x| (x, true)))
+        .collect();
+    pooled.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let mut candidate_rank_sum = 0.0;
+    let mut tie_term = 0.0;
+    let mut i = 0;
+    while i < pooled.len() {
+        let mut j = i + 1;
+        while j < pooled.len() && pooled[j].0 == pooled[i].0 {
+            j += 1;
+        }
+        // Ranks are 1-based; tied values share the average rank.
+        let rank = (i + j + 1) as f64 / 2.0;
+        let ties = (j - i) as f64;
+        tie_term += ties * ties * ties - ties;
+        candidate_rank_sum += rank * pooled[i..j].iter().filter(|p| p.1).count() as f64;
+        i = j;
+    }
+
+    let u = candidate_rank_sum - n2 * (n2 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)));
+    let (z, p_value) = if variance > 0.0 {
+        let diff = u - mean;
+        let corrected = (diff.abs() - 0.5).max(0.0) * diff.signum();
+        let z = corrected / variance.sqrt();
+        (z, erfc(z.abs() / std::f64::consts::SQRT_2).min(1.0))
+    } else {
+        (0.0, 1.0)
+    };
+
+    MannWhitney {
+        u,
+        z,
+        p_value,
+        prob_candidate_slower: u / (n1 * n2),
+    }
+}
+
+/// Percentile bootstrap intervals of the per-statistic deltas; both samples are resampled
+/// independently.
+fn bootstrap_delta_intervals(
+    baseline: &[f64],
+    candidate: &[f64],
+    resamples: usize,
+    confidence: f64,
+    seed: u64,
+) -> Vec<(f64, f64)> {
+    let mut rng = XorShift64::new(seed);
+    let mut deltas: Vec<Vec<f64>> = Vec::new();
+    let mut base_sample = vec![0.0; baseline.len()];
+    let mut cand_sample = vec![0.0; candidate.len()];
+
+    for _ in 0..resamples {
+        for slot in base_sample.iter_mut() {
+            *slot = baseline[rng.index(baseline.len())];
+        }
+        for slot in cand_sample.iter_mut() {
+            *slot = candidate[rng.index(candidate.len())];
+        }
+        let (Some(base), Some(cand)) = (
+            compute_statistics(&base_sample),
+            compute_statistics(&cand_sample),
+        ) else {
+            continue;
+        };
+        let metrics = base.metrics().into_iter().zip(cand.metrics());
+        deltas.resize_with(base.metrics().len(), || Vec::with_capacity(resamples));
+        for (k, ((_, b), (_, c))) in metrics.enumerate() {
+            deltas[k].push(c - b);
+        }
+    }
+
+    let tail = (1.0 - confidence) / 2.0 * 100.0;
+    deltas
+        .into_iter()
+        .map(|mut d| {
+            if d.is_empty() {
+                return (f64::NAN, f64::NAN);
+            }
+            d.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            (percentile(&d, tail), percentile(&d, 100.0 - tail))
+        })
+        .collect()
+}
+
+/// Filtered decode step latencies of one side of the comparison, with the window used.
+fn side_latencies(
+    label: &str,
+    input_file: &str,
+    framework: &FrameworkProfile,
+    config: &CompareConfig,
+) -> Result<(Vec<f64>, String), Box<dyn Error>> {
+    println!("\n--- {}: {} ({}) ---", label, input_file, framework);
+    let json = load_trace_json(input_file)?;
+    let trace_events = json["traceEvents"]
+        .as_array()
+        .ok_or("traceEvents not found or not an array")?;
+    let selection = extract_decode_steps(
+        trace_events,
+        framework,
+        (config.min_ms, config.max_ms),
+        config.auto_filter,
+    );
+    if selection.steps.is_empty() {
+        return Err(format!("No {} decode steps found in {}", label, input_file).into());
+    }
+    let latencies = selection.steps.iter().map(|step| step.latency_ms).collect();
+    Ok((latencies, selection.describe()))
+}
+
+/// Compute the statistics of both traces and their differences.
+pub fn compare_decode_latency(config: &CompareConfig) -> Result<Comparison, Box<dyn Error>> {
+    let (baseline, baseline_window) = side_latencies(
+        "baseline",
+        &config.baseline_file,
+        &config.baseline_framework,
+        config,
+    )?;
+    let (candidate, candidate_window) = side_latencies(
+        "candidate",
+        &config.candidate_file,
+        &config.candidate_framework,
+        config,
+    )?;
+
+    let base_stats: DecodeStats = compute_statistics(&baseline).ok_or("No baseline data")?;
+    let cand_stats: DecodeStats = compute_statistics(&candidate).ok_or("No candidate data")?;
+
+    println!(
+        "\nBootstrapping {} resamples (seed {})...",
+        config.resamples, config.seed
+    );
+    let intervals = bootstrap_delta_intervals(
+        &baseline,
+        &candidate,
+        config.resamples,
+        config.confidence,
+        config.seed,
+    );
+
+    let metrics = base_stats
+        .metrics()
+        .iter()
+        .zip(cand_stats.metrics())
+        .zip(intervals)
+        .map(|((&(statistic, b), (_, c)), (ci_low, ci_high))| {
+            let delta = c - b;
+            MetricComparison {
+                statistic,
+                baseline: b,
+                candidate: c,
+                delta,
+                change_pct: if b != 0.0 { delta / b * 100.0 } else { 0.0 },
+                ci_low,
+                ci_high,
+                significant: ci_low > 0.0 || ci_high < 0.0,
+            }
+        })
+        .collect();
+
+    Ok(Comparison {
+        baseline_count: baseline.len(),
+        candidate_count: candidate.len(),
+        metrics,
+        mann_whitney: mann_whitney(&baseline, &candidate),
+        confidence: config.confidence,
+        baseline_window,
+        candidate_window,
+    })
+}
+
+/// Print the comparison table and the verdict.
+pub fn print_comparison(comparison: &Comparison) {
+    let level = format!("{:.0}% CI of delta", comparison.confidence * 100.0);
+    println!("\n{}", "=".repeat(84));
+    println!(
+        "Decode Step Latency Comparison (baseline {} steps, candidate {} steps)",
+        comparison.baseline_count, comparison.candidate_count
+    );
+    println!("Baseline window:  {}", comparison.baseline_window);
+    println!("Candidate window: {}", comparison.candidate_window);
+    println!("{}", "=".repeat(84));
+    println!(
+        "{:<10} {:>12} {:>12} {:>12} {:>10} {:>24}",
+        "Statistic", "Base (ms)", "Cand (ms)", "Delta (ms)", "Change", level
+    );
+    for m in &comparison.metrics {
+        println!(
+            "{:<10} {:>12.3} {:>12.3} {:>+12.3} {:>+9.2}% {:>24}{}",
+            m.statistic,
+            m.baseline,
+            m.candidate,
+            m.delta,
+            m.change_pct,
+            format!("[{:+.3}, {:+.3}]", m.ci_low, m.ci_high),
+            if m.significant { " *" } else { "" }
+        );
+    }
+    println!("(* interval excludes zero)");
+
+    let mw = &comparison.mann_whitney;
+    let alpha = 1.0 - comparison.confidence;
+    println!(
+        "\nMann-Whitney U: U = {:.1}, z = {:.3}, p = {:.4}",
+        mw.u, mw.z, mw.p_value
+    );
+    println!(
+        "P(candidate step slower than baseline step) = {:.3}",
+        mw.prob_candidate_slower
+    );
+
+    let median = comparison.metrics.iter().find(|m| m.statistic == "median");
+    match median {
+        Some(m) if mw.p_value < alpha => println!(
+            "Verdict: candidate is {} (median {:+.3} ms, {:+.2}%), significant at alpha = {:.2} (p = {:.4})",
+            if mw.prob_candidate_slower > 0.5 {
+                "slower"
+            } else {
+                "faster"
+            },
+            m.delta,
+            m.change_pct,
+            alpha,
+            mw.p_value
+        ),
+        _ => println!(
+            "Verdict: no significant difference at alpha = {:.2} (p = {:.4})",
+            alpha, mw.p_value
+        ),
+    }
+}
+
+/// Write the per-statistic comparison to a CSV file.
+pub fn write_comparison_csv(
+    comparison: &Comparison,
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    println!("\nWriting comparison to CSV: {}", output_file);
+    let csv_file = File::create(output_file)?;
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(csv_file));
+
+    for record in &comparison.metrics {
+        wtr.serialize(record)?;
+    }
+
+    wtr.flush()?;
+    println!(
+        "Successfully wrote {} records to {}",
+        comparison.metrics.len(),
+        output_file
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn erfc_known_values() {
+        assert_close(erfc(0.0), 1.0, 1e-7);
+        assert_close(erfc(1.0), 0.157_299_207, 1e-7);
+        assert_close(erfc(-1.0), 1.842_700_793, 1e-7);
+        assert_close(erfc(3.0), 2.209_049_7e-5, 1e-9);
+    }
+
+    #[test]
+    fn mann_whitney_separated_samples() {
+        let result = mann_whitney(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]);
+        assert_close(result.u, 9.0, 1e-12);
+        assert_close(result.z, 1.745_743_1, 1e-6);
+        assert_close(result.p_value, 0.080_855_6, 1e-6);
+        assert_close(result.prob_candidate_slower, 1.0, 1e-12);
+
+        let reversed = mann_whitney(&[4.0, 5.0, 6.0], &[1.0, 2.0, 3.0]);
+        assert_close(reversed.u, 0.0, 1e-12);
+        assert_close(reversed.z, -1.745_743_1, 1e-6);
+        assert_close(reversed.p_value, result.p_value, 1e-12);
+    }
+
+    #[test]
+    fn mann_whitney_interleaved_samples() {
+        let result = mann_whitney(&[1.0, 3.0, 5.0, 7.0], &[2.0, 4.0, 6.0, 8.0, 9.0]);
+        assert_close(result.u, 14.0, 1e-12);
+        assert_close(result.p_value, 0.391_267_3, 1e-6);
+    }
+
+    #[test]
+    fn mann_whitney_with_ties() {
+        let result = mann_whitney(&[1.0, 1.0, 1.0, 2.0, 2.0], &[1.0, 2.0, 2.0, 2.0, 3.0]);
+        assert_close(result.u, 18.5, 1e-12);
+        assert_close(result.z, 1.270_170_6, 1e-6);
+        assert_close(result.p_value, 0.204_023_9, 1e-6);
+
+        let all_tied = mann_whitney(&[5.0; 4], &[5.0; 6]);
+        assert_close(all_tied.z, 0.0, 1e-12);
+        assert_close(all_tied.p_value, 1.0, 1e-12);
+        assert_close(all_tied.prob_candidate_slower, 0.5, 1e-12);
+    }
+
+    #[test]
+    fn bootstrap_is_deterministic_per_seed() {
+        let baseline: Vec<f64> = (0..50).map(|i| 15.0 + (i % 7) as f64 * 0.1).collect();
+        let candidate: Vec<f64> = (0..60).map(|i| 15.5 + (i % 5) as f64 * 0.1).collect();
+        let first = bootstrap_delta_intervals(&baseline, &candidate, 200, 0.95, 42);
+        let second = bootstrap_delta_intervals(&baseline, &candidate, 200, 0.95, 42);
+        let other = bootstrap_delta_intervals(&baseline, &candidate, 200, 0.95, 7);
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        // Mean delta is about +0.4 ms and clearly above zero.
+        let (low, high) = first[0];
+        assert!(low > 0.0 && low <= high, "{:?}", first[0]);
+    }
+
+    #[test]
+    fn every_seed_gives_a_working_generator() {
+        for seed in [
+            0,
+            1,
+            0x9E37_79B9_7F4A_7C15,
+            11_400_714_819_323_198_485,
+            u64::MAX,
+        ] {
+            let mut rng = XorShift64::new(seed);
+            assert_ne!(rng.0, 0);
+            let indices: Vec<usize> = (0..32).map(|_| rng.index(10)).collect();
+            assert!(indices.iter().any(|&i| i != indices[0]), "seed {}", seed);
+        }
+    }
+}
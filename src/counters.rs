@@ -0,0 +1,283 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::common::{load_trace_json, TraceEvent};
+use crate::profile_stats::{ProfileStep, StepMarker};
+
+/// Counter report configuration.
+pub struct CounterConfig {
+    pub input_file: String,
+    pub output_csv: Option<String>,
+    /// Only report series whose name matches.
+    pub counter_filter: Option<Regex>,
+    /// How step boundaries are recognized.
+    pub step_marker: StepMarker,
+    /// Report a single time range (us) instead of per-step windows.
+    pub time_window: Option<(f64, f64)>,
+}
+
+/// Identity of a counter series: `<event name>.<args key>` on a process.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SeriesKey {
+    pub name: String,
+    pub pid: String,
+}
+
+/// A counter time series; each value holds until the next sample.
+#[derive(Debug, Default)]
+pub struct CounterSeries {
+    /// `(time_us, value)` pairs sorted by time.
+    pub samples: Vec<(f64, f64)>,
+}
+
+/// Summary of one counter series over one window.
+#[derive(Debug, Serialize)]
+pub struct CounterWindowRecord {
+    pub window: String,
+    pub start_time_us: f64,
+    pub end_time_us: f64,
+    pub counter: String,
+    pub pid: String,
+    /// Samples emitted inside the window.
+    pub samples: usize,
+    pub min: f64,
+    pub max: f64,
+    /// Time-weighted average of the step function over the window.
+    pub time_weighted_avg: f64,
+    /// Value in effect at the end of the window.
+    pub last: f64,
+}
+
+/// Numeric entries of a counter event's `args` (numbers or numeric strings).
+pub fn numeric_args(args: &Map<String, Value>) -> impl Iterator<Item = (&str, f64)> {
+    args.iter().filter_map(|(key, value)| {
+        value
+            .as_f64()
+            .or_else(|| value.as_str().and_then(|s| s.trim().parse().ok()))
+            .map(|v| (key.as_str(), v))
+    })
+}
+
+/// Collect every `ph: "C"` event into named series, one per numeric `args` key.
+pub fn collect_counter_series(trace_events: &[Value]) -> BTreeMap<SeriesKey, CounterSeries> {
+    let mut series: BTreeMap<SeriesKey, CounterSeries> = BTreeMap::new();
+
+    for event_value in trace_events {
+        if event_value["ph"].as_str() != Some("C") {
+            continue;
+        }
+        let (Some(name), Some(ts), Some(args)) = (
+            event_value["name"].as_str(),
+            event_value["ts"].as_f64(),
+            event_value["args"].as_object(),
+        ) else {
+            continue;
+        };
+        let pid = match &event_value["pid"] {
+            Value::Null => String::new(),
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+
+        for (key, value) in numeric_args(args) {
+            let key = SeriesKey {
+                name: format!("{}.{}", name, key),
+                pid: pid.clone(),
+            };
+            series.entry(key).or_default().samples.push((ts, value));
+        }
+    }
+
+    for s in series.values_mut() {
+        s.samples.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    }
+    series
+}
+
+impl CounterSeries {
+    /// First value sampled in `[start, end)`, else the value in effect at `start`.
+    pub fn value_in(&self, start: f64, end: f64) -> Option<f64> {
+        let idx = self.samples.partition_point(|&(t, _)| t < start);
+        match self.samples.get(idx) {
+            Some(&(t, value)) if t < end => Some(value),
+            _ => self.value_at(start),
+        }
+    }
+
+    /// Value in effect at `time` (the last sample at or before it).
+    pub fn value_at(&self, time: f64) -> Option<f64> {
+        let idx = self.samples.partition_point(|&(t, _)| t <= time);
+        idx.checked_sub(1).map(|i| self.samples[i].1)
+    }
+
+    /// Summarize the series over `[start, end]`. Returns `None` if no value is in effect
+    /// anywhere in the window.
+    fn summarize(&self, start: f64, end: f64) -> Option<(usize, f64, f64, f64, f64)> {
+        // Value in effect at the window start, then every sample inside the window.
+        let first_inside = self.samples.partition_point(|&(t, _)| t <= start);
+        let last_inside = self.samples.partition_point(|&(t, _)| t <= end);
+        let carried = first_inside
+            .checked_sub(1)
+            .map(|i| (start, self.samples[i].1));
+        let points: Vec<(f64, f64)> = carried
+            .into_iter()
+            .chain(self.samples[first_inside..last_inside].iter().copied())
+            .collect();
+
+        let &(first_time, _) = points.first()?;
+        let min = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+        let max = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+        let last = points.last().map_or(0.0, |p| p.1);
+
+        // Integrate the step function from the first known value to the window end.
+        let mut area = 0.0;
+        for (idx, &(t, v)) in points.iter().enumerate() {
+            let next = points.get(idx + 1).map_or(end, |p| p.0);
+            area += v * (next - t);
+        }
+        let covered = end - first_time;
+        let avg = if covered > 0.0 { area / covered } else { last };
+
+        Some((last_inside - first_inside, min, max, avg, last))
+    }
+}
+
+/// Sample and time-weight-average counter series over each step (or a single window).
+pub fn analyze_counters(
+    config: &CounterConfig,
+) -> Result<Vec<CounterWindowRecord>, Box<dyn Error>> {
+    let json = load_trace_json(&config.input_file)?;
+    let trace_events = json["traceEvents"]
+        .as_array()
+        .ok_or("traceEvents not found or not an array")?;
+
+    let series = collect_counter_series(trace_events);
+    println!("Found {} counter series", series.len());
+    for (key, s) in &series {
+        println!(
+            "  {} (pid {}): {} samples",
+            key.name,
+            key.pid,
+            s.samples.len()
+        );
+    }
+
+    let windows: Vec<ProfileStep> = match config.time_window {
+        Some((start, end)) => vec![ProfileStep {
+            name: format!("{}-{}", start, end),
+            start_time: start,
+            end_time: end,
+        }],
+        None => {
+            let marker_events: Vec<ProfileStep> = trace_events
+                .iter()
+                .filter_map(|v| TraceEvent::deserialize(v).ok())
+                .filter(|e| e.ph.as_deref() == Some("X") && config.step_marker.matches(e))
+                .filter_map(|e| {
+                    e.time_range().map(|(start, end)| ProfileStep {
+                        name: e.name.clone(),
+                        start_time: start,
+                        end_time: end,
+                    })
+                })
+                .collect();
+            config.step_marker.build_steps(marker_events)
+        }
+    };
+    println!("Summarizing over {} windows", windows.len());
+
+    let mut records = Vec::new();
+    for (key, s) in &series {
+        if let Some(ref filter) = config.counter_filter {
+            if !filter.is_match(&key.name) {
+                continue;
+            }
+        }
+        for window in &windows {
+            if let Some((samples, min, max, avg, last)) =
+                s.summarize(window.start_time, window.end_time)
+            {
+                records.push(CounterWindowRecord {
+                    window: window.name.clone(),
+                    start_time_us: window.start_time,
+                    end_time_us: window.end_time,
+                    counter: key.name.clone(),
+                    pid: key.pid.clone(),
+                    samples,
+                    min,
+                    max,
+                    time_weighted_avg: avg,
+                    last,
+                });
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+/// Print the average of each counter's per-window values.
+pub fn print_counter_summary(records: &[CounterWindowRecord]) {
+    if records.is_empty() {
+        return;
+    }
+
+    let mut by_counter: BTreeMap<(&str, &str), (usize, f64, f64, f64)> = BTreeMap::new();
+    for r in records {
+        let entry = by_counter.entry((&r.counter, &r.pid)).or_insert((
+            0,
+            0.0,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ));
+        entry.0 += 1;
+        entry.1 += r.time_weighted_avg;
+        entry.2 = entry.2.min(r.min);
+        entry.3 = entry.3.max(r.max);
+    }
+
+    println!("\n--- Counter summary (average of per-window time-weighted averages) ---");
+    println!(
+        "{:<40} {:>8} {:>8} {:>14} {:>14} {:>14}",
+        "Counter", "PID", "Windows", "Avg", "Min", "Max"
+    );
+    for ((counter, pid), (windows, sum, min, max)) in by_counter {
+        println!(
+            "{:<40} {:>8} {:>8} {:>14.3} {:>14.3} {:>14.3}",
+            counter,
+            pid,
+            windows,
+            sum / windows as f64,
+            min,
+            max
+        );
+    }
+}
+
+/// Write per-window counter summaries to a CSV file.
+pub fn write_counter_csv(
+    records: &[CounterWindowRecord],
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    println!("\nWriting counter report to CSV: {}", output_file);
+    let csv_file = File::create(output_file)?;
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(csv_file));
+
+    for record in records {
+        wtr.serialize(record)?;
+    }
+
+    wtr.flush()?;
+    println!(
+        "Successfully wrote {} records to {}",
+        records.len(),
+        output_file
+    );
+
+    Ok(())
+}
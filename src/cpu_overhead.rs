@@ -0,0 +1,405 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::common::{GpuOpKind, TraceEvent};
+use crate::decode_steps::{split_python_frame, DecodeStep};
+
+/// Host-side work category of a top-level CPU function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuCategory {
+    Scheduling,
+    InputPrep,
+    Forward,
+    Sampling,
+    Output,
+    Other,
+}
+
+impl CpuCategory {
+    const ALL: [CpuCategory; 6] = [
+        CpuCategory::Scheduling,
+        CpuCategory::InputPrep,
+        CpuCategory::Forward,
+        CpuCategory::Sampling,
+        CpuCategory::Output,
+        CpuCategory::Other,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for CpuCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuCategory::Scheduling => write!(f, "scheduling"),
+            CpuCategory::InputPrep => write!(f, "input_prep"),
+            CpuCategory::Forward => write!(f, "forward"),
+            CpuCategory::Sampling => write!(f, "sampling"),
+            CpuCategory::Output => write!(f, "output"),
+            CpuCategory::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// Function-name fragments (lowercase) per category, checked in order; the first match wins.
+/// Earlier entries are more specific, e.g. `process_batch_result` is output, not scheduling.
+const CPU_CATEGORY_PATTERNS: &[(CpuCategory, &[&str])] = &[
+    (
+        CpuCategory::Sampling,
+        &["sample", "sampling", "logits", "logprob"],
+    ),
+    (
+        CpuCategory::Output,
+        &["output", "result", "detokeniz", "stream_out", "send_to"],
+    ),
+    (
+        CpuCategory::InputPrep,
+        &[
+            "prepare",
+            "input",
+            "metadata",
+            "build",
+            "init_forward",
+            "pad",
+        ],
+    ),
+    (
+        CpuCategory::Forward,
+        &[
+            "forward",
+            "run_batch",
+            "execute_model",
+            "model_executor",
+            "capture",
+            "replay",
+        ],
+    ),
+    (
+        CpuCategory::Scheduling,
+        &["schedule", "batch", "recv_req", "queue", "alloc", "prefix"],
+    ),
+];
+
+/// Steps whose GPU is busy for at least this fraction of the step are GPU-bound.
+const GPU_BOUND_MIN_BUSY_FRACTION: f64 = 0.8;
+
+/// A span starting with the step and covering at least this fraction of it is the step
+/// function itself and is expanded into its children.
+const STEP_FUNCTION_MIN_FRACTION: f64 = 0.5;
+/// Tolerance (us) when matching a span start to the step start.
+const STEP_FUNCTION_START_TOLERANCE_US: f64 = 1.0;
+
+/// Categorize a host event by its function name.
+pub fn categorize(name: &str) -> CpuCategory {
+    // Only the function part of python frames: "path/file.py(123): func" -> "func".
+    let func = split_python_frame(name)
+        .map_or(name, |(_, function)| function)
+        .to_lowercase();
+    CPU_CATEGORY_PATTERNS
+        .iter()
+        .find(|(_, patterns)| patterns.iter().any(|p| func.contains(p)))
+        .map_or(CpuCategory::Other, |(category, _)| *category)
+}
+
+/// Host and GPU time breakdown of one decode step (all times in ms).
+#[derive(Debug, Clone)]
+pub struct StepBreakdown {
+    pub step_index: usize,
+    pub start_time_us: f64,
+    pub latency_ms: f64,
+    /// Time of top-level host functions, indexed by `CpuCategory`.
+    pub category_ms: [f64; 6],
+    /// Step time not covered by any top-level host function.
+    pub host_idle_ms: f64,
+    /// Union of GPU operation time within the step.
+    pub gpu_busy_ms: f64,
+}
+
+impl StepBreakdown {
+    pub fn gpu_busy_fraction(&self) -> f64 {
+        if self.latency_ms > 0.0 {
+            self.gpu_busy_ms / self.latency_ms
+        } else {
+            0.0
+        }
+    }
+
+    pub fn is_gpu_bound(&self) -> bool {
+        self.gpu_busy_fraction() >= GPU_BOUND_MIN_BUSY_FRACTION
+    }
+}
+
+/// Breakdown CSV row.
+#[derive(Debug, Serialize)]
+struct BreakdownRecord {
+    step_index: usize,
+    start_time_us: f64,
+    latency_ms: f64,
+    scheduling_ms: f64,
+    input_prep_ms: f64,
+    forward_ms: f64,
+    sampling_ms: f64,
+    output_ms: f64,
+    other_ms: f64,
+    host_idle_ms: f64,
+    gpu_busy_ms: f64,
+    gpu_busy_pct: f64,
+    bound: &'static str,
+}
+
+/// A host span on some thread.
+struct HostSpan {
+    name: String,
+    pid: Option<Value>,
+    tid: Option<Value>,
+    start: f64,
+    end: f64,
+}
+
+/// Outermost spans of a list sorted by start time (ties: longer first).
+fn outermost<'a>(sorted: impl Iterator<Item = &'a HostSpan>) -> Vec<&'a HostSpan> {
+    let mut result: Vec<&HostSpan> = Vec::new();
+    let mut covered_until = f64::NEG_INFINITY;
+    for span in sorted {
+        if span.start >= covered_until {
+            covered_until = span.end;
+            result.push(span);
+        }
+    }
+    result
+}
+
+/// Top-level host spans on the step's thread that lie fully inside the step window.
+/// `spans` must be sorted by start time.
+/// Spans enclosing the whole window (e.g. the scheduler event loop) are ignored, and the
+/// step function itself (starting with the step and covering most of it) is replaced by
+/// its children.
+fn top_level_spans<'a>(spans: &'a [HostSpan], step: &DecodeStep) -> Vec<&'a HostSpan> {
+    let (start, end) = (step.start_time, step.end_time());
+    let first = spans.partition_point(|s| s.start < start);
+    let last = spans.partition_point(|s| s.start <= end);
+    let mut inside: Vec<&HostSpan> = spans[first..last]
+        .iter()
+        .filter(|s| s.pid == step.pid && s.tid == step.tid)
+        .filter(|s| s.start >= start && s.end <= end)
+        .collect();
+    inside.sort_by(|a, b| {
+        a.start
+            .partial_cmp(&b.start)
+            .unwrap()
+            .then(b.end.partial_cmp(&a.end).unwrap())
+    });
+
+    let mut top_level: Vec<&HostSpan> = Vec::new();
+    for span in outermost(inside.iter().copied()) {
+        let is_step_function = (span.start - start).abs() <= STEP_FUNCTION_START_TOLERANCE_US
+            && span.end - span.start >= STEP_FUNCTION_MIN_FRACTION * (end - start);
+        let children =
+            if is_step_function {
+                outermost(inside.iter().copied().filter(|s| {
+                    !std::ptr::eq(*s, span) && s.start >= span.start && s.end <= span.end
+                }))
+            } else {
+                Vec::new()
+            };
+        if children.is_empty() {
+            top_level.push(span);
+        } else {
+            top_level.extend(children);
+        }
+    }
+    top_level
+}
+
+/// Union length (us) of `[start, end)` intervals clipped to the window.
+fn busy_time(mut intervals: Vec<(f64, f64)>, start: f64, end: f64) -> f64 {
+    intervals.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let mut total = 0.0;
+    let mut current: Option<(f64, f64)> = None;
+    for (s, e) in intervals {
+        let (s, e) = (s.max(start), e.min(end));
+        if e <= s {
+            continue;
+        }
+        current = match current {
+            Some((cs, ce)) if s <= ce => Some((cs, ce.max(e))),
+            Some((cs, ce)) => {
+                total += ce - cs;
+                Some((s, e))
+            }
+            None => Some((s, e)),
+        };
+    }
+    if let Some((cs, ce)) = current {
+        total += ce - cs;
+    }
+    total
+}
+
+/// Break each step down into top-level host categories and GPU busy time.
+pub fn step_breakdowns(trace_events: &[Value], steps: &[DecodeStep]) -> Vec<StepBreakdown> {
+    let mut host_spans: Vec<HostSpan> = Vec::new();
+    let mut gpu_intervals: Vec<(f64, f64)> = Vec::new();
+
+    for event_value in trace_events {
+        let event: TraceEvent = match TraceEvent::deserialize(event_value) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        if event.ph.as_deref() != Some("X") {
+            continue;
+        }
+        let Some((start, end)) = event.time_range() else {
+            continue;
+        };
+        if event
+            .cat
+            .as_deref()
+            .and_then(GpuOpKind::from_category)
+            .is_some()
+        {
+            gpu_intervals.push((start, end));
+        } else {
+            host_spans.push(HostSpan {
+                name: event.name,
+                pid: event.pid,
+                tid: event.tid,
+                start,
+                end,
+            });
+        }
+    }
+    host_spans.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+    gpu_intervals.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let max_gpu_duration = gpu_intervals.iter().map(|(s, e)| e - s).fold(0.0, f64::max);
+
+    steps
+        .iter()
+        .enumerate()
+        .map(|(step_index, step)| {
+            let (start, end) = (step.start_time, step.end_time());
+
+            let mut category_ms = [0.0; 6];
+            let mut host_us = 0.0;
+            for span in top_level_spans(&host_spans, step) {
+                let duration = span.end - span.start;
+                category_ms[categorize(&span.name).index()] += duration / 1000.0;
+                host_us += duration;
+            }
+
+            // GPU operations overlapping the window; long operations may start before it.
+            let first = gpu_intervals.partition_point(|(s, _)| *s < start - max_gpu_duration);
+            let last = gpu_intervals.partition_point(|(s, _)| *s < end);
+            let overlapping = gpu_intervals[first..last].to_vec();
+
+            StepBreakdown {
+                step_index,
+                start_time_us: start,
+                latency_ms: step.latency_ms,
+                category_ms,
+                host_idle_ms: ((end - start) - host_us).max(0.0) / 1000.0,
+                gpu_busy_ms: busy_time(overlapping, start, end) / 1000.0,
+            }
+        })
+        .collect()
+}
+
+/// Print the average host breakdown and GPU busy time over all steps.
+pub fn print_breakdown_summary(breakdowns: &[StepBreakdown]) {
+    if breakdowns.is_empty() {
+        println!("\nCPU breakdown: no steps");
+        return;
+    }
+
+    let n = breakdowns.len() as f64;
+    let avg_latency = breakdowns.iter().map(|b| b.latency_ms).sum::<f64>() / n;
+    let percent = |ms: f64| {
+        if avg_latency > 0.0 {
+            ms / avg_latency * 100.0
+        } else {
+            0.0
+        }
+    };
+
+    println!("\n{}", "=".repeat(60));
+    println!("CPU Overhead Breakdown (average per step)");
+    println!("{}", "=".repeat(60));
+    println!("{:<14} {:>12} {:>10}", "Category", "Avg (ms)", "% of step");
+    for category in CpuCategory::ALL {
+        let avg = breakdowns
+            .iter()
+            .map(|b| b.category_ms[category.index()])
+            .sum::<f64>()
+            / n;
+        println!(
+            "{:<14} {:>12.3} {:>9.1}%",
+            category.to_string(),
+            avg,
+            percent(avg)
+        );
+    }
+    let idle = breakdowns.iter().map(|b| b.host_idle_ms).sum::<f64>() / n;
+    println!("{:<14} {:>12.3} {:>9.1}%", "host_idle", idle, percent(idle));
+    let gpu = breakdowns.iter().map(|b| b.gpu_busy_ms).sum::<f64>() / n;
+    println!("{:<14} {:>12.3} {:>9.1}%", "gpu_busy", gpu, percent(gpu));
+
+    let gpu_bound = breakdowns.iter().filter(|b| b.is_gpu_bound()).count();
+    println!(
+        "\nGPU-bound steps (GPU busy >= {:.0}% of step): {} / {}",
+        GPU_BOUND_MIN_BUSY_FRACTION * 100.0,
+        gpu_bound,
+        breakdowns.len()
+    );
+    println!(
+        "Verdict: {}",
+        if gpu_bound * 2 >= breakdowns.len() {
+            "decode is mostly GPU-bound"
+        } else {
+            "decode is mostly CPU-bound"
+        }
+    );
+}
+
+/// Write per-step breakdowns to a CSV file.
+pub fn write_breakdown_csv(
+    breakdowns: &[StepBreakdown],
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    println!("\nWriting CPU breakdown to CSV: {}", output_file);
+    let csv_file = File::create(output_file)?;
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(csv_file));
+
+    for b in breakdowns {
+        let c = |category: CpuCategory| b.category_ms[category.index()];
+        wtr.serialize(BreakdownRecord {
+            step_index: b.step_index,
+            start_time_us: b.start_time_us,
+            latency_ms: b.latency_ms,
+            scheduling_ms: c(CpuCategory::Scheduling),
+            input_prep_ms: c(CpuCategory::InputPrep),
+            forward_ms: c(CpuCategory::Forward),
+            sampling_ms: c(CpuCategory::Sampling),
+            output_ms: c(CpuCategory::Output),
+            other_ms: c(CpuCategory::Other),
+            host_idle_ms: b.host_idle_ms,
+            gpu_busy_ms: b.gpu_busy_ms,
+            gpu_busy_pct: b.gpu_busy_fraction() * 100.0,
+            bound: if b.is_gpu_bound() { "gpu" } else { "cpu" },
+        })?;
+    }
+
+    wtr.flush()?;
+    println!(
+        "Successfully wrote {} records to {}",
+        breakdowns.len(),
+        output_file
+    );
+
+    Ok(())
+}
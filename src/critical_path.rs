@@ -0,0 +1,320 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::common::GpuOperation;
+
+/// A cross-stream dependency inferred without sync events must start within this
+/// many microseconds of the producing operation's end.
+const INFER_TOLERANCE_US: f64 = 5.0;
+
+/// Cross-stream wait recorded in the trace: work issued to `stream` after `time`
+/// waits for work already issued to `wait_on_stream`.
+#[derive(Debug, Clone)]
+pub struct StreamWait {
+    pub device: Option<i64>,
+    pub stream: Option<i64>,
+    pub wait_on_stream: i64,
+    pub time: f64,
+}
+
+/// Why an operation on the critical path could not start earlier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dependency {
+    /// First operation of the path; only bounded by the step start.
+    StepStart,
+    /// Previous operation on the same stream.
+    Stream,
+    /// Explicit cross-stream wait event.
+    Wait,
+    /// Cross-stream dependency inferred from back-to-back timing.
+    Inferred,
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dependency::StepStart => write!(f, "step_start"),
+            Dependency::Stream => write!(f, "stream"),
+            Dependency::Wait => write!(f, "wait"),
+            Dependency::Inferred => write!(f, "inferred"),
+        }
+    }
+}
+
+/// One operation on a step's critical path.
+#[derive(Debug, Clone)]
+pub struct PathNode {
+    pub op: GpuOperation,
+    /// Idle time between the gating predecessor (or step start) and this operation.
+    pub gap_before: f64,
+    pub dependency: Dependency,
+}
+
+/// Per-step critical path record for CSV output.
+#[derive(Debug, Serialize)]
+pub struct CriticalPathRecord {
+    pub step_index: usize,
+    pub step_name: String,
+    pub phase: String,
+    pub position: usize,
+    pub kernel_name: String,
+    pub stream: Option<i64>,
+    pub start_us: f64,
+    pub end_us: f64,
+    pub duration_us: f64,
+    pub gap_before_us: f64,
+    pub dependency: String,
+}
+
+/// Compute the chain of operations and gaps that determines when a step's GPU work ends.
+///
+/// `ops` must be sorted by start time and share a time base with `waits`. Each operation
+/// depends on the previous operation of its stream and, across streams, on the producer
+/// named by a wait event. When the trace has no wait events, an operation that starts
+/// right after another stream's operation ends while its own stream was idle is treated
+/// as dependent on it. Walking back from the last-finishing operation through the
+/// latest-ending predecessor yields the critical path.
+pub fn critical_path(ops: &[GpuOperation], waits: &[StreamWait]) -> Vec<PathNode> {
+    if ops.is_empty() {
+        return Vec::new();
+    }
+
+    // Operation indices per (device, stream), in start order.
+    let mut by_stream: HashMap<(Option<i64>, Option<i64>), Vec<usize>> = HashMap::new();
+    let mut prev_on_stream: Vec<Option<usize>> = Vec::with_capacity(ops.len());
+    for (idx, op) in ops.iter().enumerate() {
+        let list = by_stream.entry((op.device, op.stream)).or_default();
+        prev_on_stream.push(list.last().copied());
+        list.push(idx);
+    }
+
+    // Operation indices sorted by end time, for inferred dependencies.
+    let mut by_end: Vec<usize> = (0..ops.len()).collect();
+    by_end.sort_by(|&a, &b| ops[a].end_time.partial_cmp(&ops[b].end_time).unwrap());
+
+    let predecessors = |idx: usize| -> Vec<(usize, Dependency)> {
+        let op = &ops[idx];
+        let prev = prev_on_stream[idx];
+        let mut preds: Vec<(usize, Dependency)> =
+            prev.map(|p| (p, Dependency::Stream)).into_iter().collect();
+
+        if !waits.is_empty() {
+            // Waits issued on this stream between the previous operation and this one.
+            let prev_start = prev.map_or(f64::NEG_INFINITY, |p| ops[p].start_time);
+            for wait in waits.iter().filter(|w| {
+                w.device == op.device
+                    && w.stream == op.stream
+                    && w.time > prev_start
+                    && w.time <= op.start_time
+            }) {
+                if let Some(producers) = by_stream.get(&(op.device, Some(wait.wait_on_stream))) {
+                    let n = producers.partition_point(|&p| ops[p].start_time < wait.time);
+                    if n > 0 {
+                        preds.push((producers[n - 1], Dependency::Wait));
+                    }
+                }
+            }
+        } else {
+            // Only infer when the own stream did not gate the start.
+            let own_ready = prev.map_or(f64::NEG_INFINITY, |p| ops[p].end_time);
+            if op.start_time - own_ready > INFER_TOLERANCE_US {
+                let lo = by_end
+                    .partition_point(|&p| ops[p].end_time < op.start_time - INFER_TOLERANCE_US);
+                let hi = by_end.partition_point(|&p| ops[p].end_time <= op.start_time);
+                // Producers must end by the consumer's start and start strictly before it;
+                // operations that start together are concurrent, not dependent.
+                if let Some(&producer) = by_end[lo..hi].iter().rev().find(|&&p| {
+                    ops[p].start_time < op.start_time
+                        && ops[p].end_time <= op.start_time
+                        && ops[p].device == op.device
+                        && ops[p].stream != op.stream
+                }) {
+                    preds.push((producer, Dependency::Inferred));
+                }
+            }
+        }
+
+        preds
+    };
+
+    // Walk back from the operation that finishes last.
+    let mut current = (0..ops.len())
+        .max_by(|&a, &b| ops[a].end_time.partial_cmp(&ops[b].end_time).unwrap())
+        .unwrap();
+    let mut path: Vec<PathNode> = Vec::new();
+    let mut visited = vec![false; ops.len()];
+
+    loop {
+        visited[current] = true;
+        let gating = predecessors(current)
+            .into_iter()
+            .max_by(|a, b| ops[a.0].end_time.partial_cmp(&ops[b.0].end_time).unwrap());
+
+        let op = ops[current].clone();
+        match gating {
+            // A repeated node would mean a dependency cycle; end the walk there.
+            Some((pred, dependency)) if !visited[pred] => {
+                path.push(PathNode {
+                    gap_before: (op.start_time - ops[pred].end_time).max(0.0),
+                    op,
+                    dependency,
+                });
+                current = pred;
+            }
+            _ => {
+                path.push(PathNode {
+                    gap_before: op.start_time.max(0.0),
+                    op,
+                    dependency: Dependency::StepStart,
+                });
+                break;
+            }
+        }
+    }
+
+    path.reverse();
+    path
+}
+
+/// Print the most common critical path of a phase with per-position averages.
+pub fn print_average_critical_path(paths: &[&[PathNode]], phase: &str) {
+    println!("\n--- Critical path ({} steps) ---", phase);
+
+    let mut signature_counts: HashMap<Vec<&str>, usize> = HashMap::new();
+    for path in paths.iter().filter(|p| !p.is_empty()) {
+        let signature: Vec<&str> = path.iter().map(|n| n.op.name.as_str()).collect();
+        *signature_counts.entry(signature).or_insert(0) += 1;
+    }
+
+    let Some((signature, count)) = signature_counts.into_iter().max_by_key(|(_, c)| *c) else {
+        println!("No GPU operations found");
+        return;
+    };
+
+    let matching: Vec<&&[PathNode]> = paths
+        .iter()
+        .filter(|p| {
+            p.len() == signature.len() && p.iter().zip(&signature).all(|(n, s)| n.op.name == *s)
+        })
+        .collect();
+    let n = matching.len() as f64;
+
+    println!(
+        "Most common path: {} operations, shared by {} of {} steps",
+        signature.len(),
+        count,
+        paths.len()
+    );
+    println!(
+        "{:<50} {:>8} {:>12} {:>12} {:>12} {:>12}",
+        "Operation", "Stream", "Start(us)", "End(us)", "Dur(us)", "Gap(us)"
+    );
+    println!("{}", "-".repeat(111));
+
+    let mut total_duration = 0.0;
+    let mut total_gap = 0.0;
+    for (pos, name) in signature.iter().enumerate() {
+        let avg =
+            |f: &dyn Fn(&PathNode) -> f64| matching.iter().map(|p| f(&p[pos])).sum::<f64>() / n;
+        let duration = avg(&|node| node.op.duration);
+        let gap = avg(&|node| node.gap_before);
+        total_duration += duration;
+        total_gap += gap;
+
+        let display_name = if name.len() > 47 {
+            format!("{}...", &name[..47])
+        } else {
+            name.to_string()
+        };
+        let stream = matching[0][pos]
+            .op
+            .stream
+            .map_or("-".to_string(), |s| s.to_string());
+        println!(
+            "{:<50} {:>8} {:>12.3} {:>12.3} {:>12.3} {:>12.3}",
+            display_name,
+            stream,
+            avg(&|node| node.op.start_time),
+            avg(&|node| node.op.end_time),
+            duration,
+            gap
+        );
+    }
+
+    println!(
+        "Path total: {:.3} us kernel time + {:.3} us gaps = {:.3} us",
+        total_duration,
+        total_gap,
+        total_duration + total_gap
+    );
+}
+
+/// Write per-step critical paths to a CSV file.
+pub fn write_critical_path_csv(
+    records: &[CriticalPathRecord],
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    println!("Writing critical paths to CSV file: {}", output_file);
+    let csv_file = File::create(output_file)?;
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(csv_file));
+
+    for record in records {
+        wtr.serialize(record)?;
+    }
+
+    wtr.flush()?;
+    println!(
+        "Successfully wrote {} records to {}",
+        records.len(),
+        output_file
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::GpuOpKind;
+
+    fn op(name: &str, stream: i64, start: f64, end: f64) -> GpuOperation {
+        GpuOperation {
+            name: name.to_string(),
+            start_time: start,
+            end_time: end,
+            duration: end - start,
+            kind: GpuOpKind::Kernel,
+            device: Some(0),
+            stream: Some(stream),
+        }
+    }
+
+    fn names(path: &[PathNode]) -> Vec<&str> {
+        path.iter().map(|n| n.op.name.as_str()).collect()
+    }
+
+    #[test]
+    fn ops_starting_together_are_concurrent() {
+        let ops = vec![op("a", 1, 100.0, 100.0), op("b", 2, 100.0, 100.0)];
+        let path = critical_path(&ops, &[]);
+        assert_eq!(names(&path), ["b"]);
+        assert_eq!(path[0].dependency, Dependency::StepStart);
+    }
+
+    #[test]
+    fn inferred_dependency_follows_producer_stream() {
+        let ops = vec![
+            op("producer", 1, 0.0, 50.0),
+            op("side", 2, 10.0, 20.0),
+            op("consumer", 2, 52.0, 80.0),
+        ];
+        let path = critical_path(&ops, &[]);
+        assert_eq!(names(&path), ["producer", "consumer"]);
+        assert_eq!(path[1].dependency, Dependency::Inferred);
+        assert!((path[1].gap_before - 2.0).abs() < 1e-9);
+    }
+}
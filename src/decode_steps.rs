@@ -1,51 +1,201 @@
-use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
 use std::io::BufWriter;
 
+use crate::batch::{annotate_steps, print_batch_report, write_batch_csv};
 use crate::common::{load_trace_json, TraceEvent};
+use crate::cpu_overhead::{print_breakdown_summary, step_breakdowns, write_breakdown_csv};
+use crate::drift::{latency_series, print_drift_summary, write_series_csv};
+use crate::framework::{FrameworkProfile, StepMatcher, StepStrategy, WarmupRule};
+use crate::phase::decode_duration_range;
+use crate::plot::{print_distribution, write_svg, Distribution};
+use crate::steady_state::{changepoints, steady_segment};
+use crate::summary::{write_summary_json, write_summary_yaml, DecodeSummary};
+
+/// Split a python stack event name "path/file.py(123): func" into ("path/file.py", "func").
+pub fn split_python_frame(name: &str) -> Option<(&str, &str)> {
+    let (location, function) = name.split_once("): ")?;
+    let (file, line) = location.rsplit_once('(')?;
+    if line.is_empty() || !line.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((file, function))
+}
 
-/// Supported inference frameworks.
-#[derive(Debug, Clone, Copy)]
-pub enum Framework {
-    Sglang,
-    Vllm,
-    Fastdeploy,
+/// A python function matched by file path suffix and function name, ignoring the line
+/// number so that upstream edits to the file do not break step detection.
+#[derive(Debug, Clone)]
+pub struct FunctionMarker {
+    pub file: String,
+    pub function: String,
 }
 
-impl fmt::Display for Framework {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Framework::Sglang => write!(f, "sglang"),
-            Framework::Vllm => write!(f, "vllm"),
-            Framework::Fastdeploy => write!(f, "fastdeploy"),
+impl FunctionMarker {
+    pub fn new(file: &str, function: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            function: function.to_string(),
+        }
+    }
+
+    /// Parse `<file path suffix>:<function>`, e.g. `vllm/v1/engine/core.py:step`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec.rsplit_once(':') {
+            Some((file, function)) if !file.is_empty() && !function.is_empty() => {
+                Ok(Self::new(file, function))
+            }
+            _ => Err(format!(
+                "Invalid marker '{}': expected <file>:<function>",
+                spec
+            )),
         }
     }
+
+    /// Whether an event name is a call of this function, at any line.
+    pub fn matches(&self, name: &str) -> bool {
+        split_python_frame(name).is_some_and(|(file, function)| {
+            function == self.function
+                && file.ends_with(&self.file)
+                && (file.len() == self.file.len()
+                    || file[..file.len() - self.file.len()].ends_with('/'))
+        })
+    }
+}
+
+impl fmt::Display for FunctionMarker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.function)
+    }
+}
+
+/// Minimum number of calls for a function to be listed as a step-loop candidate.
+const MIN_LOOP_CANDIDATE_CALLS: usize = 10;
+/// Median interval (ms) between calls of a step-loop candidate.
+const LOOP_CANDIDATE_INTERVAL_MS: (f64, f64) = (1.0, 1000.0);
+/// Name fragments of typical scheduler-loop functions, listed first.
+const LOOP_FUNCTION_HINTS: &[&str] = &["step", "batch", "schedul", "loop"];
+/// Number of step-loop candidates printed.
+const MAX_LOOP_CANDIDATES: usize = 10;
+
+/// A python function that is called periodically and may delimit steps.
+struct LoopCandidate {
+    marker: FunctionMarker,
+    calls: usize,
+    median_interval_ms: f64,
+    hinted: bool,
 }
 
-impl Framework {
-    pub fn from_str(s: &str) -> Option<Self> {
-        match s.to_lowercase().as_str() {
-            "sglang" => Some(Framework::Sglang),
-            "vllm" => Some(Framework::Vllm),
-            "fastdeploy" | "fd" => Some(Framework::Fastdeploy),
-            _ => None,
+/// Find python functions called at a steady, step-like rate.
+fn discover_loop_candidates(events: &[Value]) -> Vec<LoopCandidate> {
+    let mut starts: HashMap<(&str, &str), Vec<f64>> = HashMap::new();
+    for event_value in events {
+        if event_value["ph"].as_str() != Some("X") {
+            continue;
+        }
+        let (Some(name), Some(ts)) = (event_value["name"].as_str(), event_value["ts"].as_f64())
+        else {
+            continue;
+        };
+        if let Some(frame) = split_python_frame(name) {
+            starts.entry(frame).or_default().push(ts);
         }
     }
+
+    let mut candidates: Vec<LoopCandidate> = starts
+        .into_iter()
+        .filter(|(_, ts)| ts.len() >= MIN_LOOP_CANDIDATE_CALLS)
+        .filter_map(|((file, function), mut ts)| {
+            ts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let mut intervals: Vec<f64> = ts.windows(2).map(|w| (w[1] - w[0]) / 1000.0).collect();
+            intervals.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let median_interval_ms = percentile(&intervals, 50.0);
+            let (min_ms, max_ms) = LOOP_CANDIDATE_INTERVAL_MS;
+            if median_interval_ms < min_ms || median_interval_ms > max_ms {
+                return None;
+            }
+            let lower = function.to_lowercase();
+            Some(LoopCandidate {
+                marker: FunctionMarker::new(file, function),
+                calls: ts.len(),
+                median_interval_ms,
+                hinted: LOOP_FUNCTION_HINTS.iter().any(|hint| lower.contains(hint)),
+            })
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| {
+        b.hinted
+            .cmp(&a.hinted)
+            .then(b.calls.cmp(&a.calls))
+            .then(a.marker.to_string().cmp(&b.marker.to_string()))
+    });
+    candidates
+}
+
+/// Print step-loop candidates when the configured marker is absent from the trace.
+fn print_loop_candidates(events: &[Value], marker: &StepMatcher) {
+    println!("\nMarker '{}' not found in trace.", marker);
+    let candidates = discover_loop_candidates(events);
+    if candidates.is_empty() {
+        println!(
+            "No periodically called python functions found (is python stack tracing enabled?)"
+        );
+        return;
+    }
+
+    println!("Candidate step-loop functions (use --marker <file>:<function>):");
+    for candidate in candidates.iter().take(MAX_LOOP_CANDIDATES) {
+        println!(
+            "  {:<80} calls {:>6}, median interval {:>9.3} ms",
+            candidate.marker.to_string(),
+            candidate.calls,
+            candidate.median_interval_ms
+        );
+    }
 }
 
 /// Configuration for decode step analysis.
 pub struct DecodeStepsConfig {
-    pub framework: Framework,
+    pub framework: FrameworkProfile,
     pub input_file: String,
     pub output_csv: Option<String>,
     pub min_ms: f64,
     pub max_ms: f64,
+    /// Derive the filter bounds from the decode duration cluster instead of `min_ms`/`max_ms`.
+    pub auto_filter: bool,
+    /// Break each step's time down into host categories versus GPU busy time.
+    pub cpu_breakdown: bool,
+    /// Optional per-step CSV output of the breakdown.
+    pub breakdown_csv: Option<String>,
+    /// Report the linear trend and changepoints of the latency series.
+    pub drift: bool,
+    /// Optional per-step CSV output of the latency series.
+    pub series_csv: Option<String>,
+    /// Number of steps in the rolling statistics of the series.
+    pub rolling_window: usize,
+    /// Annotate steps with batch size and token counts and report latency per batch size.
+    pub batch_report: bool,
+    /// Optional per-step CSV output of the annotations.
+    pub batch_csv: Option<String>,
+    /// Width of the batch size buckets.
+    pub batch_bucket: usize,
+    /// Render a histogram and CDF of the filtered latencies.
+    pub histogram: bool,
+    /// Histogram bucket width in ms (automatic if unset).
+    pub bucket_ms: Option<f64>,
+    /// Optional SVG output of the histogram and CDF.
+    pub svg: Option<String>,
+    /// Optional machine-readable summary outputs.
+    pub summary_json: Option<String>,
+    pub summary_yaml: Option<String>,
 }
 
 /// Statistics computed from decode step latencies.
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct DecodeStats {
     pub count: usize,
     pub mean: f64,
@@ -58,26 +208,56 @@ pub struct DecodeStats {
     pub p99: f64,
 }
 
-/// Extract decode step latencies from sglang trace.
-/// Uses time intervals between consecutive 'get_next_batch_to_run' event starts.
-/// Only uses the first 50% of the time range to ensure steps are fully loaded.
-fn parse_sglang_steps(events: &[serde_json::Value]) -> Vec<f64> {
-    const TARGET: &str = "python/sglang/srt/managers/scheduler.py(2071): get_next_batch_to_run";
-    extract_interval_latencies(events, TARGET)
+impl DecodeStats {
+    /// Latency statistics (ms) by name, in report order.
+    pub fn metrics(&self) -> [(&'static str, f64); 8] {
+        [
+            ("mean", self.mean),
+            ("std_dev", self.std_dev),
+            ("min", self.min),
+            ("max", self.max),
+            ("median", self.median),
+            ("p90", self.p90),
+            ("p95", self.p95),
+            ("p99", self.p99),
+        ]
+    }
+}
+
+/// A detected decode step window.
+#[derive(Debug, Clone)]
+pub struct DecodeStep {
+    /// Position among all steps detected in the trace, before any filtering.
+    pub index: usize,
+    /// Start of the step (us).
+    pub start_time: f64,
+    pub latency_ms: f64,
+    /// Process and thread of the marker event, used to attribute host time.
+    pub pid: Option<Value>,
+    pub tid: Option<Value>,
+}
+
+impl DecodeStep {
+    pub fn end_time(&self) -> f64 {
+        self.start_time + self.latency_ms * 1000.0
+    }
 }
 
-/// Extract decode step latencies from vllm trace.
-/// Uses time intervals between consecutive 'step_with_batch_queue' event starts.
-/// Only uses the first 50% of the time range to ensure steps are fully loaded.
-fn parse_vllm_steps(events: &[serde_json::Value]) -> Vec<f64> {
-    const TARGET: &str = "vllm/v1/engine/core.py(421): step_with_batch_queue";
-    extract_interval_latencies(events, TARGET)
+/// Marker event reduced to what step extraction needs (times in us).
+struct MarkerEvent {
+    start: f64,
+    duration: Option<f64>,
+    pid: Option<Value>,
+    tid: Option<Value>,
 }
 
-/// Shared logic for sglang/vllm: collect timestamps of a named event, use first 50%
-/// of the time range, then compute consecutive intervals in ms.
-fn extract_interval_latencies(events: &[serde_json::Value], target_name: &str) -> Vec<f64> {
-    let mut timestamps: Vec<f64> = Vec::new();
+/// Detect step windows with the framework profile's marker and strategy.
+/// Interval: a step runs from one marker start to the next (e.g. sglang's
+/// `get_next_batch_to_run`, vllm's `step_with_batch_queue`).
+/// Duration: each marker event is a step (e.g. FastDeploy's `ProfileStep#N[...ms]`).
+/// All steps are returned; see `select_steady_state` for dropping warm-up and cool-down.
+pub fn parse_steps(events: &[Value], profile: &FrameworkProfile) -> Vec<DecodeStep> {
+    let mut markers: Vec<MarkerEvent> = Vec::new();
 
     for event_value in events {
         let event: TraceEvent = match TraceEvent::deserialize(event_value) {
@@ -85,64 +265,180 @@ fn extract_interval_latencies(events: &[serde_json::Value], target_name: &str) -
             Err(_) => continue,
         };
 
-        if event.name == target_name && event.ph.as_deref() == Some("X") {
+        if profile.matches(&event) {
             if let Some(ts) = event.ts {
-                timestamps.push(ts);
+                markers.push(MarkerEvent {
+                    start: profile.units.to_us(ts),
+                    duration: event.dur.map(|dur| profile.units.to_us(dur)),
+                    pid: event.pid,
+                    tid: event.tid,
+                });
             }
         }
     }
 
-    if timestamps.len() < 2 {
-        return Vec::new();
+    markers.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+
+    match profile.strategy {
+        StepStrategy::Interval => markers
+            .windows(2)
+            .enumerate()
+            .map(|(index, w)| DecodeStep {
+                index,
+                start_time: w[0].start,
+                latency_ms: (w[1].start - w[0].start) / 1000.0, // μs → ms
+                pid: w[0].pid.clone(),
+                tid: w[0].tid.clone(),
+            })
+            .collect(),
+        StepStrategy::Duration => markers
+            .into_iter()
+            .filter_map(|m| Some((m.duration?, m)))
+            .enumerate()
+            .map(|(index, (duration, m))| DecodeStep {
+                index,
+                start_time: m.start,
+                latency_ms: duration / 1000.0, // μs → ms
+                pid: m.pid,
+                tid: m.tid,
+            })
+            .collect(),
     }
+}
 
-    timestamps.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+/// Steps kept after applying a warm-up rule.
+pub struct SteadyWindow {
+    /// Number of steps before the rule was applied.
+    pub total: usize,
+    /// Index of the first kept step among all steps.
+    pub first: usize,
+    /// Rules that dropped steps, in the order applied.
+    pub applied: Vec<String>,
+}
 
-    // Only keep the first 50% of the time range.
-    let time_range = timestamps[timestamps.len() - 1] - timestamps[0];
-    let cutoff = timestamps[0] + time_range / 2.0;
-    timestamps.retain(|&ts| ts <= cutoff);
+/// Apply a warm-up rule: time offsets, step counts, time fraction, then steady-state detection.
+pub fn select_steady_state(
+    steps: Vec<DecodeStep>,
+    rule: &WarmupRule,
+) -> (Vec<DecodeStep>, SteadyWindow) {
+    let total = steps.len();
+    let mut first = 0;
+    let mut applied: Vec<String> = Vec::new();
+    let mut kept = steps;
+
+    // Retain the contiguous range [lo, hi) and record the rule if it dropped anything.
+    let mut keep_range = |kept: &mut Vec<DecodeStep>, lo: usize, hi: usize, label: String| {
+        let hi = hi.max(lo);
+        if lo > 0 || hi < kept.len() {
+            applied.push(label);
+        }
+        first += lo;
+        kept.truncate(hi);
+        kept.drain(..lo);
+    };
 
-    // Compute intervals between consecutive timestamps (μs → ms).
-    timestamps
-        .windows(2)
-        .map(|w| (w[1] - w[0]) / 1000.0)
-        .collect()
-}
+    if let (Some(start), Some(end)) = (
+        kept.first().map(|s| s.start_time),
+        kept.iter().map(DecodeStep::end_time).reduce(f64::max),
+    ) {
+        if rule.skip_start_ms > 0.0 || rule.skip_end_ms > 0.0 {
+            let from = start + rule.skip_start_ms * 1000.0;
+            let until = end - rule.skip_end_ms * 1000.0;
+            let lo = kept.partition_point(|s| s.start_time < from);
+            let hi = kept.partition_point(|s| s.end_time() <= until);
+            let label = format!(
+                "skip {} ms at start / {} ms at end",
+                rule.skip_start_ms, rule.skip_end_ms
+            );
+            keep_range(&mut kept, lo, hi, label);
+        }
+    }
 
-/// Extract decode step latencies from fastdeploy trace.
-/// Uses the `dur` field from `ProfileStep#N[...ms]` events directly.
-fn parse_fastdeploy_steps(events: &[serde_json::Value]) -> Vec<f64> {
-    let pattern = Regex::new(r"^ProfileStep#\d+\[[\d.]+\s*ms\]").unwrap();
-    let mut durations: Vec<f64> = Vec::new();
+    if rule.warmup_steps > 0 || rule.cooldown_steps > 0 {
+        let len = kept.len();
+        let lo = rule.warmup_steps.min(len);
+        let hi = len.saturating_sub(rule.cooldown_steps);
+        let label = format!(
+            "{} warm-up / {} cool-down steps",
+            rule.warmup_steps, rule.cooldown_steps
+        );
+        keep_range(&mut kept, lo, hi, label);
+    }
 
-    for event_value in events {
-        let event: TraceEvent = match TraceEvent::deserialize(event_value) {
-            Ok(e) => e,
-            Err(_) => continue,
-        };
+    if let (Some(fraction), Some(step)) = (rule.keep_time_fraction, kept.first()) {
+        let start = step.start_time;
+        let end = kept.iter().map(DecodeStep::end_time).fold(start, f64::max);
+        let cutoff = start + (end - start) * fraction;
+        let hi = kept.partition_point(|s| s.end_time() <= cutoff);
+        let label = format!("first {:.0}% of time range", fraction * 100.0);
+        let len = kept.len();
+        keep_range(&mut kept, 0, hi.min(len), label);
+    }
 
-        if pattern.is_match(&event.name) && event.ph.as_deref() == Some("X") {
-            if let Some(dur) = event.dur {
-                durations.push(dur / 1000.0); // μs → ms
-            }
+    if rule.detect_steady_state {
+        let latencies: Vec<f64> = kept.iter().map(|s| s.latency_ms).collect();
+        let (lo, hi) = steady_segment(&latencies);
+        let cuts = changepoints(&latencies).len();
+        let label = format!("steady-state detection ({} changepoints)", cuts);
+        if lo == 0 && hi == kept.len() {
+            applied.push(label);
+        } else {
+            keep_range(&mut kept, lo, hi, label);
         }
     }
 
-    durations
+    let window = SteadyWindow {
+        total,
+        first,
+        applied,
+    };
+    (kept, window)
 }
 
-/// Filter latencies to keep only valid decode steps within [min_ms, max_ms].
-fn filter_decode_steps(latencies: &[f64], min_ms: f64, max_ms: f64) -> Vec<f64> {
-    latencies
+/// Print which steps were kept and why.
+fn print_steady_window(steps: &[DecodeStep], window: &SteadyWindow, trace_start: f64) {
+    let (Some(first), Some(last)) = (steps.first(), steps.last()) else {
+        println!("Steady-state window: no steps kept of {}", window.total);
+        return;
+    };
+    println!(
+        "Steady-state window: steps {}-{} of {} ({:.3}-{:.3} ms after the first step), dropped {} leading and {} trailing",
+        window.first,
+        window.first + steps.len() - 1,
+        window.total,
+        (first.start_time - trace_start) / 1000.0,
+        (last.end_time() - trace_start) / 1000.0,
+        window.first,
+        window.total - window.first - steps.len()
+    );
+    if window.applied.is_empty() {
+        println!("  rule: keep all steps");
+    } else {
+        println!("  rule: {}", window.applied.join(", then "));
+    }
+}
+
+/// Detect step windows and keep those in the decode latency cluster.
+pub fn decode_step_windows(events: &[Value], profile: &FrameworkProfile) -> Vec<DecodeStep> {
+    let (steps, _) = select_steady_state(parse_steps(events, profile), &profile.warmup);
+    let latencies: Vec<f64> = steps.iter().map(|step| step.latency_ms).collect();
+    match decode_duration_range(&latencies) {
+        Some((min_ms, max_ms)) => filter_decode_steps(&steps, min_ms, max_ms),
+        None => steps,
+    }
+}
+
+/// Filter steps to keep only valid decode steps within [min_ms, max_ms].
+fn filter_decode_steps(steps: &[DecodeStep], min_ms: f64, max_ms: f64) -> Vec<DecodeStep> {
+    steps
         .iter()
-        .copied()
-        .filter(|&lat| lat >= min_ms && lat <= max_ms)
+        .filter(|step| step.latency_ms >= min_ms && step.latency_ms <= max_ms)
+        .cloned()
         .collect()
 }
 
 /// Compute the p-th percentile using linear interpolation (matches numpy default).
-fn percentile(sorted: &[f64], p: f64) -> f64 {
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
     assert!(!sorted.is_empty());
     if sorted.len() == 1 {
         return sorted[0];
@@ -159,7 +455,7 @@ fn percentile(sorted: &[f64], p: f64) -> f64 {
 }
 
 /// Compute statistics from a list of latencies.
-fn compute_statistics(latencies: &[f64]) -> Option<DecodeStats> {
+pub fn compute_statistics(latencies: &[f64]) -> Option<DecodeStats> {
     if latencies.is_empty() {
         return None;
     }
@@ -185,7 +481,7 @@ fn compute_statistics(latencies: &[f64]) -> Option<DecodeStats> {
 }
 
 /// Print statistics in the same format as the Python script.
-fn print_statistics(stats: &DecodeStats, name: &str) {
+pub fn print_statistics(stats: &DecodeStats, name: &str) {
     println!("\n{}", "=".repeat(60));
     println!("{} Decode Step Statistics", name);
     println!("{}", "=".repeat(60));
@@ -216,48 +512,275 @@ fn write_latencies_csv(latencies: &[f64], output_file: &str) -> Result<(), Box<d
     Ok(())
 }
 
-/// Main entry point: analyze decode step latencies for a single framework.
-pub fn analyze_decode_steps(config: &DecodeStepsConfig) -> Result<(), Box<dyn Error>> {
-    let json = load_trace_json(&config.input_file)?;
-    let trace_events = json["traceEvents"]
-        .as_array()
-        .ok_or("traceEvents not found or not an array")?;
+/// Decode steps selected from a trace, with how they were selected.
+pub struct DecodeSelection {
+    /// Steps kept after the warm-up rule and the latency filter.
+    pub steps: Vec<DecodeStep>,
+    /// Steady-state window applied before filtering.
+    pub window: SteadyWindow,
+    /// Number of steps in the window, before the latency filter.
+    pub window_steps: usize,
+    /// Start of the first and end of the last step in the window (us).
+    pub window_range: Option<(f64, f64)>,
+    /// Latency filter bounds applied (ms).
+    pub filter: (f64, f64),
+    /// All detected steps, before the warm-up rule and the latency filter.
+    pub detected: Vec<DecodeStep>,
+}
 
-    println!("\nExtracting {} decode step latencies...", config.framework);
+/// Describe a steady-state window and latency filter in one line, for reports.
+pub fn describe_window(
+    first: usize,
+    steps: usize,
+    total: usize,
+    rules: &[String],
+    filter: (f64, f64),
+) -> String {
+    let range = if steps == 0 {
+        format!("no steps kept of {}", total)
+    } else {
+        format!("steps {}-{} of {}", first, first + steps - 1, total)
+    };
+    let rules = if rules.is_empty() {
+        "keep all steps".to_string()
+    } else {
+        rules.join(", then ")
+    };
+    format!(
+        "{} ({}), filter {:.1}-{:.1} ms",
+        range, rules, filter.0, filter.1
+    )
+}
 
-    let raw_latencies = match config.framework {
-        Framework::Sglang => parse_sglang_steps(&trace_events),
-        Framework::Vllm => parse_vllm_steps(&trace_events),
-        Framework::Fastdeploy => parse_fastdeploy_steps(&trace_events),
+impl DecodeSelection {
+    /// The window and filter that selected the steps, see `describe_window`.
+    pub fn describe(&self) -> String {
+        describe_window(
+            self.window.first,
+            self.window_steps,
+            self.window.total,
+            &self.window.applied,
+            self.filter,
+        )
+    }
+}
+
+/// Detect steps, apply the warm-up rule and keep those within the latency filter,
+/// printing what was dropped at each stage.
+pub fn extract_decode_steps(
+    trace_events: &[Value],
+    framework: &FrameworkProfile,
+    filter: (f64, f64),
+    auto_filter: bool,
+) -> DecodeSelection {
+    println!("\nExtracting {} decode step latencies...", framework);
+
+    let all_steps = parse_steps(trace_events, framework);
+    if all_steps.is_empty() {
+        print_loop_candidates(trace_events, &framework.marker);
+    }
+    let trace_start = all_steps.first().map_or(0.0, |s| s.start_time);
+    let (raw_steps, window) = select_steady_state(all_steps.clone(), &framework.warmup);
+    print_steady_window(&raw_steps, &window, trace_start);
+
+    println!("\nRaw count (before filtering): {} steps", raw_steps.len());
+    let raw_latencies: Vec<f64> = raw_steps.iter().map(|step| step.latency_ms).collect();
+
+    let (min_ms, max_ms) = if auto_filter {
+        let range = decode_duration_range(&raw_latencies).unwrap_or(filter);
+        println!(
+            "Automatic filter: decode cluster spans {:.3}-{:.3}ms",
+            range.0, range.1
+        );
+        range
+    } else {
+        filter
     };
 
+    let filtered_steps = filter_decode_steps(&raw_steps, min_ms, max_ms);
     println!(
-        "\nRaw count (before filtering): {} steps",
-        raw_latencies.len()
+        "Filtered count (keeping {:.1}-{:.1}ms): {} steps (removed {})",
+        min_ms,
+        max_ms,
+        filtered_steps.len(),
+        raw_steps.len() - filtered_steps.len()
     );
+    DecodeSelection {
+        steps: filtered_steps,
+        window,
+        window_steps: raw_steps.len(),
+        window_range: raw_steps
+            .first()
+            .zip(raw_steps.last())
+            .map(|(first, last)| (first.start_time, last.end_time())),
+        filter: (min_ms, max_ms),
+        detected: all_steps,
+    }
+}
 
-    let filtered = filter_decode_steps(&raw_latencies, config.min_ms, config.max_ms);
+/// Main entry point: analyze decode step latencies for a single framework.
+pub fn analyze_decode_steps(config: &DecodeStepsConfig) -> Result<(), Box<dyn Error>> {
+    let json = load_trace_json(&config.input_file)?;
+    let trace_events = json["traceEvents"]
+        .as_array()
+        .ok_or("traceEvents not found or not an array")?;
 
-    println!(
-        "Filtered count (keeping {:.1}-{:.1}ms): {} steps (removed {})",
-        config.min_ms,
-        config.max_ms,
-        filtered.len(),
-        raw_latencies.len() - filtered.len()
+    let selection = extract_decode_steps(
+        trace_events,
+        &config.framework,
+        (config.min_ms, config.max_ms),
+        config.auto_filter,
     );
+    let filtered_steps = &selection.steps;
+    let filtered: Vec<f64> = filtered_steps.iter().map(|step| step.latency_ms).collect();
 
-    match compute_statistics(&filtered) {
-        Some(stats) => {
-            print_statistics(&stats, &config.framework.to_string());
+    let stats = compute_statistics(&filtered);
+    match stats {
+        Some(ref stats) => {
+            print_statistics(stats, &config.framework.to_string());
         }
         None => {
             println!("\n{}: No data available", config.framework);
         }
     }
 
+    if config.summary_json.is_some() || config.summary_yaml.is_some() {
+        let summary = DecodeSummary::new(config, &selection, stats)?;
+        if let Some(ref path) = config.summary_json {
+            write_summary_json(&summary, path)?;
+        }
+        if let Some(ref path) = config.summary_yaml {
+            write_summary_yaml(&summary, path)?;
+        }
+    }
+
+    if config.histogram {
+        let dist = Distribution {
+            label: format!("{} decode step latency", config.framework),
+            unit: "ms",
+            values: filtered.clone(),
+        };
+        print_distribution(&dist, config.bucket_ms);
+        if let Some(ref svg_path) = config.svg {
+            write_svg(&[dist], config.bucket_ms, svg_path)?;
+        }
+    }
+
     if let Some(ref csv_path) = config.output_csv {
         write_latencies_csv(&filtered, csv_path)?;
     }
 
+    if config.drift {
+        // Steady-state detection would keep a single segment and hide the drift, so the
+        // series only applies the other warm-up rules and the latency filter.
+        let mut rule = config.framework.warmup.clone();
+        rule.detect_steady_state = false;
+        let (drift_steps, _) = select_steady_state(selection.detected.clone(), &rule);
+        let (min_ms, max_ms) = selection.filter;
+        let drift_steps = filter_decode_steps(&drift_steps, min_ms, max_ms);
+        println!(
+            "\nDrift series: {} steps (warm-up rules without steady-state detection, filter {:.1}-{:.1}ms)",
+            drift_steps.len(),
+            min_ms,
+            max_ms
+        );
+        if config.framework.warmup.detect_steady_state {
+            println!(
+                "Steady-state window not applied to drift; the statistics above use {}",
+                selection.describe()
+            );
+        }
+        let series = latency_series(&drift_steps, config.rolling_window);
+        print_drift_summary(&series);
+        if let Some(ref csv_path) = config.series_csv {
+            write_series_csv(&series, csv_path)?;
+        }
+    }
+
+    if config.batch_report {
+        let annotated = annotate_steps(trace_events, filtered_steps, config.framework.units);
+        print_batch_report(&annotated, config.batch_bucket);
+        if let Some(ref csv_path) = config.batch_csv {
+            write_batch_csv(&annotated, csv_path)?;
+        }
+    }
+
+    if config.cpu_breakdown {
+        let breakdowns = step_breakdowns(trace_events, filtered_steps);
+        print_breakdown_summary(&breakdowns);
+        if let Some(ref csv_path) = config.breakdown_csv {
+            write_breakdown_csv(&breakdowns, csv_path)?;
+        }
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steps 30 ms apart with the given latencies.
+    fn steps(latencies: &[f64]) -> Vec<DecodeStep> {
+        latencies
+            .iter()
+            .enumerate()
+            .map(|(index, &latency_ms)| DecodeStep {
+                index,
+                start_time: index as f64 * 30_000.0,
+                latency_ms,
+                pid: None,
+                tid: None,
+            })
+            .collect()
+    }
+
+    fn flat(n: usize) -> Vec<f64> {
+        (0..n).map(|i| 15.0 + (i % 3) as f64 * 0.05).collect()
+    }
+
+    #[test]
+    fn skip_start_then_warmup_steps() {
+        let rule = WarmupRule {
+            skip_start_ms: 100.0,
+            warmup_steps: 3,
+            cooldown_steps: 2,
+            ..Default::default()
+        };
+        let (kept, window) = select_steady_state(steps(&flat(50)), &rule);
+        // Steps starting at 0-90 ms are skipped, then 3 more steps of warm-up.
+        assert_eq!(window.first, 7);
+        assert_eq!(kept.first().unwrap().index, 7);
+        assert_eq!(kept.last().unwrap().index, 47);
+        assert_eq!(kept.len(), 41);
+        assert_eq!(window.total, 50);
+        assert_eq!(window.applied.len(), 2);
+    }
+
+    #[test]
+    fn steady_state_detection_offsets_add_up() {
+        let mut latencies = vec![25.0; 40];
+        latencies.extend(flat(60));
+        let rule = WarmupRule {
+            warmup_steps: 8,
+            detect_steady_state: true,
+            ..Default::default()
+        };
+        let (kept, window) = select_steady_state(steps(&latencies), &rule);
+        assert_eq!(window.first, 40);
+        assert_eq!(kept.first().unwrap().index, 40);
+        assert_eq!(kept.len(), 60);
+        assert_eq!(window.applied.len(), 2);
+    }
+
+    #[test]
+    fn steady_state_detection_keeps_flat_series() {
+        let rule = WarmupRule {
+            detect_steady_state: true,
+            ..Default::default()
+        };
+        let (kept, window) = select_steady_state(steps(&flat(100)), &rule);
+        assert_eq!(kept.len(), 100);
+        assert_eq!(window.first, 0);
+    }
+}
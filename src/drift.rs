@@ -0,0 +1,276 @@
+use serde::Serialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::decode_steps::{percentile, DecodeStep};
+use crate::steady_state::changepoints;
+
+/// One decode step of the latency series with trailing rolling statistics.
+#[derive(Debug, Serialize)]
+pub struct SeriesPoint {
+    pub step_index: usize,
+    pub start_time_us: f64,
+    /// Time since the first step in the series.
+    pub elapsed_ms: f64,
+    pub latency_ms: f64,
+    pub rolling_mean_ms: f64,
+    pub rolling_p50_ms: f64,
+    pub rolling_p90_ms: f64,
+    pub rolling_p99_ms: f64,
+}
+
+/// Least-squares fit of latency against time.
+pub struct LinearTrend {
+    pub slope_ms_per_s: f64,
+    pub intercept_ms: f64,
+    pub r_squared: f64,
+}
+
+/// A run of steps between two changepoints.
+pub struct LatencySegment {
+    /// Range in the series, `[first, last]`.
+    pub first: usize,
+    pub last: usize,
+    pub mean_ms: f64,
+}
+
+/// Build the latency series; rolling statistics cover the last `window` steps
+/// (fewer at the start).
+pub fn latency_series(steps: &[DecodeStep], window: usize) -> Vec<SeriesPoint> {
+    let Some(origin) = steps.first().map(|s| s.start_time) else {
+        return Vec::new();
+    };
+    let window = window.max(1);
+    let latencies: Vec<f64> = steps.iter().map(|s| s.latency_ms).collect();
+
+    steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let recent = &latencies[(i + 1).saturating_sub(window)..=i];
+            let mut sorted = recent.to_vec();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            SeriesPoint {
+                step_index: step.index,
+                start_time_us: step.start_time,
+                elapsed_ms: (step.start_time - origin) / 1000.0,
+                latency_ms: step.latency_ms,
+                rolling_mean_ms: recent.iter().sum::<f64>() / recent.len() as f64,
+                rolling_p50_ms: percentile(&sorted, 50.0),
+                rolling_p90_ms: percentile(&sorted, 90.0),
+                rolling_p99_ms: percentile(&sorted, 99.0),
+            }
+        })
+        .collect()
+}
+
+/// Fit `latency = intercept + slope * elapsed`; `None` if all steps start together.
+pub fn linear_trend(series: &[SeriesPoint]) -> Option<LinearTrend> {
+    let n = series.len() as f64;
+    if series.len() < 2 {
+        return None;
+    }
+    // Elapsed time in seconds.
+    let xs: Vec<f64> = series.iter().map(|p| p.elapsed_ms / 1000.0).collect();
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = series.iter().map(|p| p.latency_ms).sum::<f64>() / n;
+
+    let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+    for (x, point) in xs.iter().zip(series) {
+        let dx = x - mean_x;
+        let dy = point.latency_ms - mean_y;
+        sxx += dx * dx;
+        sxy += dx * dy;
+        syy += dy * dy;
+    }
+    if sxx <= 0.0 {
+        return None;
+    }
+
+    let slope = sxy / sxx;
+    Some(LinearTrend {
+        slope_ms_per_s: slope,
+        intercept_ms: mean_y - slope * mean_x,
+        r_squared: if syy > 0.0 {
+            sxy * sxy / (sxx * syy)
+        } else {
+            0.0
+        },
+    })
+}
+
+/// Split the series at latency changepoints.
+pub fn latency_segments(series: &[SeriesPoint]) -> Vec<LatencySegment> {
+    let latencies: Vec<f64> = series.iter().map(|p| p.latency_ms).collect();
+    let cuts = changepoints(&latencies);
+    let bounds: Vec<usize> = std::iter::once(0)
+        .chain(cuts)
+        .chain(std::iter::once(latencies.len()))
+        .collect();
+    bounds
+        .windows(2)
+        .filter(|w| w[1] > w[0])
+        .map(|w| LatencySegment {
+            first: w[0],
+            last: w[1] - 1,
+            mean_ms: latencies[w[0]..w[1]].iter().sum::<f64>() / (w[1] - w[0]) as f64,
+        })
+        .collect()
+}
+
+/// Print the linear trend and where latency shifted.
+pub fn print_drift_summary(series: &[SeriesPoint]) {
+    let (Some(first), Some(last)) = (series.first(), series.last()) else {
+        return;
+    };
+
+    println!("\n--- Latency drift ---");
+    let duration_s = (last.elapsed_ms - first.elapsed_ms) / 1000.0;
+    match linear_trend(series) {
+        Some(trend) => {
+            let start = trend.intercept_ms;
+            let change = trend.slope_ms_per_s * duration_s;
+            println!(
+                "Linear trend: {:+.4} ms/s (R² = {:.3}), {:.3} -> {:.3} ms over {:.1} s ({:+.3} ms, {:+.2}%)",
+                trend.slope_ms_per_s,
+                trend.r_squared,
+                start,
+                start + change,
+                duration_s,
+                change,
+                if start != 0.0 { change / start * 100.0 } else { 0.0 }
+            );
+        }
+        None => println!("Linear trend: not enough distinct timestamps"),
+    }
+
+    let segments = latency_segments(series);
+    if segments.len() <= 1 {
+        println!("Changepoints: none, latency level is stable");
+        return;
+    }
+    println!("Changepoints: {}", segments.len() - 1);
+    println!(
+        "{:>12} {:>12} {:>14} {:>12} {:>10}",
+        "Steps", "Count", "From (ms)", "Mean (ms)", "Shift"
+    );
+    let mut previous: Option<f64> = None;
+    for segment in &segments {
+        let shift = previous.map_or("-".to_string(), |p| {
+            format!("{:+.2}%", (segment.mean_ms - p) / p * 100.0)
+        });
+        println!(
+            "{:>12} {:>12} {:>14.3} {:>12.3} {:>10}",
+            format!(
+                "{}-{}",
+                series[segment.first].step_index, series[segment.last].step_index
+            ),
+            segment.last - segment.first + 1,
+            series[segment.first].elapsed_ms,
+            segment.mean_ms,
+            shift
+        );
+        previous = Some(segment.mean_ms);
+    }
+}
+
+/// Write the per-step latency series to a CSV file.
+pub fn write_series_csv(series: &[SeriesPoint], output_file: &str) -> Result<(), Box<dyn Error>> {
+    println!("\nWriting latency series to CSV: {}", output_file);
+    let csv_file = File::create(output_file)?;
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(csv_file));
+
+    for point in series {
+        wtr.serialize(point)?;
+    }
+
+    wtr.flush()?;
+    println!(
+        "Successfully wrote {} records to {}",
+        series.len(),
+        output_file
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steps 100 ms apart with the given latencies, numbered from 5.
+    fn steps(latencies: &[f64]) -> Vec<DecodeStep> {
+        latencies
+            .iter()
+            .enumerate()
+            .map(|(i, &latency_ms)| DecodeStep {
+                index: i + 5,
+                start_time: 1_000_000.0 + i as f64 * 100_000.0,
+                latency_ms,
+                pid: None,
+                tid: None,
+            })
+            .collect()
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn rolling_statistics_cover_trailing_window() {
+        let series = latency_series(&steps(&[1.0, 2.0, 3.0, 4.0, 5.0]), 3);
+        assert_eq!(series.len(), 5);
+
+        // Fewer steps at the start.
+        assert_eq!(series[0].rolling_mean_ms, 1.0);
+        assert_eq!(series[0].rolling_p99_ms, 1.0);
+        assert_eq!(series[1].rolling_p50_ms, 1.5);
+
+        // Window [3, 4, 5].
+        let last = &series[4];
+        assert_eq!((last.step_index, last.elapsed_ms), (9, 400.0));
+        assert_eq!(last.rolling_mean_ms, 4.0);
+        assert_eq!(last.rolling_p50_ms, 4.0);
+        assert!(close(last.rolling_p90_ms, 4.8));
+        assert!(close(last.rolling_p99_ms, 4.98));
+    }
+
+    #[test]
+    fn trend_slope_in_ms_per_second() {
+        // +0.5 ms per second of run time, steps every 0.1 s.
+        let latencies: Vec<f64> = (0..50).map(|i| 10.0 + 0.05 * i as f64).collect();
+        let trend = linear_trend(&latency_series(&steps(&latencies), 10)).unwrap();
+        assert!(close(trend.slope_ms_per_s, 0.5));
+        assert!(close(trend.intercept_ms, 10.0));
+        assert!(close(trend.r_squared, 1.0));
+
+        let flat = linear_trend(&latency_series(&steps(&[12.0; 20]), 10)).unwrap();
+        assert!(close(flat.slope_ms_per_s, 0.0));
+        assert_eq!(flat.r_squared, 0.0);
+    }
+
+    #[test]
+    fn trend_needs_distinct_timestamps() {
+        assert!(linear_trend(&latency_series(&steps(&[12.0]), 10)).is_none());
+        let mut same_start = steps(&[12.0, 13.0]);
+        same_start[1].start_time = same_start[0].start_time;
+        assert!(linear_trend(&latency_series(&same_start, 10)).is_none());
+    }
+
+    #[test]
+    fn segments_split_at_level_shift() {
+        let latencies: Vec<f64> = (0..120)
+            .map(|i| if i < 60 { 10.0 } else { 12.0 } + (i % 3) as f64 * 0.01)
+            .collect();
+        let segments = latency_segments(&latency_series(&steps(&latencies), 10));
+        let bounds: Vec<(usize, usize)> = segments.iter().map(|s| (s.first, s.last)).collect();
+        assert_eq!(bounds, [(0, 59), (60, 119)]);
+        assert!(close(segments[0].mean_ms, 10.01));
+        assert!(close(segments[1].mean_ms, 12.01));
+
+        let flat = latency_segments(&latency_series(&steps(&[12.0; 80]), 10));
+        assert_eq!(flat.len(), 1);
+    }
+}
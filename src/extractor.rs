@@ -3,7 +3,7 @@ use std::error::Error;
 use std::fs::File;
 use std::io::BufWriter;
 
-use crate::common::{load_trace_json, parse_time_from_string, TraceEvent};
+use crate::common::{load_trace_json, operations_within, GpuOperation, TraceEvent};
 
 /// Output kernel record.
 #[derive(Debug, Serialize)]
@@ -34,8 +34,8 @@ pub fn extract_kernels(config: &ExtractConfig) -> Result<Vec<KernelRecord>, Box<
         .as_array()
         .ok_or("traceEvents not found or not an array")?;
 
-    // Collect matching kernel records.
-    let mut kernel_records: Vec<KernelRecord> = Vec::new();
+    // Collect all GPU operations, then select the time range from the sorted list.
+    let mut gpu_operations: Vec<GpuOperation> = Vec::new();
     let mut processed = 0;
     let total = trace_events.len();
 
@@ -51,36 +51,35 @@ pub fn extract_kernels(config: &ExtractConfig) -> Result<Vec<KernelRecord>, Box<
         };
 
         // Filter criteria:
-        // 1. Category is "Kernel", "Memcpy", or "Memset".
-        // 2. Phase is "X" (complete event).
-        // 3. Contains args with start_time and end_time.
-        if let (Some(cat), Some(ph), Some(args)) = (&event.cat, &event.ph, &event.args) {
-            let is_gpu_operation = cat == "Kernel" || cat == "Memcpy" || cat == "Memset";
-
-            if is_gpu_operation && ph == "X" {
-                if let (Some(start_str), Some(end_str)) = (&args.start_time, &args.end_time) {
-                    if let (Some(start), Some(end)) = (
-                        parse_time_from_string(start_str),
-                        parse_time_from_string(end_str),
-                    ) {
-                        // Check time range.
-                        if start >= config.start_time && end <= config.end_time {
-                            let duration = end - start;
-                            kernel_records.push(KernelRecord {
-                                kernel_name: event.name.clone(),
-                                start_time_us: start,
-                                end_time_us: end,
-                                duration_us: duration,
-                            });
-                        }
-                    }
-                }
-            }
+        // 1. Category is a GPU operation (kernel, memcpy or memset).
+        // 2. Phase is "X" (complete event, including normalized B/E and b/e spans).
+        // 3. Has a time range from args start_time/end_time or ts/dur.
+        if event.ph.as_deref() != Some("X") {
+            continue;
+        }
+        if let Some((start, end)) = event.time_range() {
+            gpu_operations.extend(GpuOperation::from_event(
+                &event,
+                event.name.clone(),
+                start,
+                end,
+            ));
         }
     }
 
     // Sort by start time.
-    kernel_records.sort_by(|a, b| a.start_time_us.partial_cmp(&b.start_time_us).unwrap());
+    gpu_operations.sort_by(|a, b| a.start_time.partial_cmp(&b.start_time).unwrap());
+
+    // Check time range.
+    let kernel_records: Vec<KernelRecord> =
+        operations_within(&gpu_operations, config.start_time, config.end_time)
+            .map(|op| KernelRecord {
+                kernel_name: op.name.clone(),
+                start_time_us: op.start_time,
+                end_time_us: op.end_time,
+                duration_us: op.duration,
+            })
+            .collect();
 
     println!(
         "Found {} kernel events in the specified time range",
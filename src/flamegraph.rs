@@ -0,0 +1,288 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::common::{load_trace_json, normalize_op_name, GpuOpKind, TraceEvent};
+use crate::decode_steps::decode_step_windows;
+use crate::framework::FrameworkProfile;
+
+/// Flame graph export configuration.
+pub struct FlameGraphConfig {
+    pub input_file: String,
+    /// Folded-stack output (one `frame;frame;... <self us>` line per stack).
+    pub output_folded: String,
+    pub table_csv: Option<String>,
+    /// Only count time inside this range (us).
+    pub time_window: Option<(f64, f64)>,
+    /// Only count time inside the decode steps detected for this framework.
+    pub decode_framework: Option<FrameworkProfile>,
+    /// Number of functions printed in the self/total table.
+    pub top: usize,
+}
+
+/// A host span placed in its thread's call tree.
+struct CallNode {
+    name: String,
+    start: f64,
+    end: f64,
+    parent: Option<usize>,
+}
+
+/// Aggregated time of one function.
+#[derive(Debug, Serialize)]
+pub struct FunctionRecord {
+    pub function: String,
+    pub calls: usize,
+    /// Inclusive time, counting recursive calls once.
+    pub total_us: f64,
+    /// Exclusive time (total minus children).
+    pub self_us: f64,
+}
+
+/// Sorted, non-overlapping time windows that time is restricted to.
+struct Windows(Vec<(f64, f64)>);
+
+impl Windows {
+    /// Length of `[start, end]` inside the windows.
+    fn clip(&self, start: f64, end: f64) -> f64 {
+        if self.0.is_empty() {
+            return end - start;
+        }
+        let first = self.0.partition_point(|w| w.1 <= start);
+        self.0[first..]
+            .iter()
+            .take_while(|w| w.0 < end)
+            .map(|w| (end.min(w.1) - start.max(w.0)).max(0.0))
+            .sum()
+    }
+}
+
+/// Rebuild the call tree of one thread from span nesting.
+/// Spans that partially overlap their would-be parent are treated as siblings.
+fn build_call_tree(mut spans: Vec<(String, f64, f64)>) -> Vec<CallNode> {
+    // Parents first: earlier start, then longer duration.
+    spans.sort_by(|a, b| {
+        a.1.partial_cmp(&b.1)
+            .unwrap()
+            .then(b.2.partial_cmp(&a.2).unwrap())
+    });
+
+    let mut nodes: Vec<CallNode> = Vec::with_capacity(spans.len());
+    let mut stack: Vec<usize> = Vec::new();
+    for (name, start, end) in spans {
+        while let Some(&top) = stack.last() {
+            if nodes[top].end >= end && nodes[top].end > start {
+                break;
+            }
+            stack.pop();
+        }
+        nodes.push(CallNode {
+            name,
+            start,
+            end,
+            parent: stack.last().copied(),
+        });
+        stack.push(nodes.len() - 1);
+    }
+    nodes
+}
+
+/// Rebuild per-thread call trees from nested host `X` events, write folded stacks and
+/// return the aggregated self/total table sorted by self time.
+pub fn export_flamegraph(config: &FlameGraphConfig) -> Result<Vec<FunctionRecord>, Box<dyn Error>> {
+    let json = load_trace_json(&config.input_file)?;
+    let trace_events = json["traceEvents"]
+        .as_array()
+        .ok_or("traceEvents not found or not an array")?;
+
+    let mut windows: Vec<(f64, f64)> = Vec::new();
+    if let Some(window) = config.time_window {
+        windows.push(window);
+    }
+    if let Some(ref framework) = config.decode_framework {
+        let steps = decode_step_windows(trace_events, framework);
+        println!("Restricting to {} {} decode steps", steps.len(), framework);
+        if steps.is_empty() {
+            return Err(format!("No {} decode steps found", framework).into());
+        }
+        let step_windows = steps.iter().map(|s| (s.start_time, s.end_time()));
+        windows = match config.time_window {
+            Some((start, end)) => step_windows
+                .map(|(s, e)| (s.max(start), e.min(end)))
+                .filter(|(s, e)| e > s)
+                .collect(),
+            None => step_windows.collect(),
+        };
+        if windows.is_empty() {
+            return Err(format!("No {} decode steps overlap the time window", framework).into());
+        }
+    }
+    windows.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let restricted = config.time_window.is_some() || config.decode_framework.is_some();
+    let windows = Windows(windows);
+
+    // Group host spans by thread.
+    let mut threads: BTreeMap<String, Vec<(String, f64, f64)>> = BTreeMap::new();
+    for event_value in trace_events {
+        let event: TraceEvent = match TraceEvent::deserialize(event_value) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        if event.ph.as_deref() != Some("X")
+            || event
+                .cat
+                .as_deref()
+                .and_then(GpuOpKind::from_category)
+                .is_some()
+        {
+            continue;
+        }
+        let Some((start, end)) = event.time_range() else {
+            continue;
+        };
+        let thread = format!(
+            "thread {}:{}",
+            event.pid.as_ref().map_or(String::new(), Value::to_string),
+            event.tid.as_ref().map_or(String::new(), Value::to_string)
+        )
+        .replace('"', "");
+        // ';' separates frames in the folded format.
+        let name = normalize_op_name(&event.name).replace(';', ":");
+        threads.entry(thread).or_default().push((name, start, end));
+    }
+
+    let mut folded: BTreeMap<String, f64> = BTreeMap::new();
+    let mut functions: HashMap<String, FunctionRecord> = HashMap::new();
+    let mut span_count = 0;
+
+    for (thread, spans) in threads {
+        let nodes = build_call_tree(spans);
+
+        let totals: Vec<f64> = nodes.iter().map(|n| windows.clip(n.start, n.end)).collect();
+        let mut child_totals = vec![0.0; nodes.len()];
+        for (idx, node) in nodes.iter().enumerate() {
+            if let Some(parent) = node.parent {
+                child_totals[parent] += totals[idx];
+            }
+        }
+
+        // Stack paths, built parent-first (parents precede children after sorting).
+        let mut paths: Vec<String> = Vec::with_capacity(nodes.len());
+        for node in &nodes {
+            let prefix = node.parent.map_or(thread.as_str(), |p| paths[p].as_str());
+            paths.push(format!("{};{}", prefix, node.name));
+        }
+
+        for (idx, node) in nodes.iter().enumerate() {
+            if restricted && totals[idx] <= 0.0 {
+                continue;
+            }
+            span_count += 1;
+            let self_time = (totals[idx] - child_totals[idx]).max(0.0);
+            *folded.entry(paths[idx].clone()).or_insert(0.0) += self_time;
+
+            // Recursive calls only count towards total time at the outermost level.
+            let mut ancestor = node.parent;
+            let mut recursive = false;
+            while let Some(a) = ancestor {
+                if nodes[a].name == node.name {
+                    recursive = true;
+                    break;
+                }
+                ancestor = nodes[a].parent;
+            }
+
+            let record = functions
+                .entry(node.name.clone())
+                .or_insert_with(|| FunctionRecord {
+                    function: node.name.clone(),
+                    calls: 0,
+                    total_us: 0.0,
+                    self_us: 0.0,
+                });
+            record.calls += 1;
+            record.self_us += self_time;
+            if !recursive {
+                record.total_us += totals[idx];
+            }
+        }
+    }
+
+    println!("Built call trees from {} host spans", span_count);
+    write_folded(&folded, &config.output_folded)?;
+
+    let mut records: Vec<FunctionRecord> = functions.into_values().collect();
+    records.sort_by(|a, b| b.self_us.partial_cmp(&a.self_us).unwrap());
+    Ok(records)
+}
+
+/// Write folded stacks; values are self time in whole microseconds.
+fn write_folded(folded: &BTreeMap<String, f64>, output_file: &str) -> Result<(), Box<dyn Error>> {
+    println!("Writing folded stacks to: {}", output_file);
+    let mut writer = BufWriter::new(File::create(output_file)?);
+    let mut lines = 0;
+    for (stack, &self_us) in folded {
+        let value = self_us.round() as u64;
+        if value > 0 {
+            writeln!(writer, "{} {}", stack, value)?;
+            lines += 1;
+        }
+    }
+    writer.flush()?;
+    println!("Successfully wrote {} stacks to {}", lines, output_file);
+    Ok(())
+}
+
+/// Print the functions with the largest self time.
+pub fn print_function_table(records: &[FunctionRecord], top: usize) {
+    if records.is_empty() {
+        return;
+    }
+
+    println!(
+        "\n--- Top {} functions by self time ---",
+        top.min(records.len())
+    );
+    println!(
+        "{:<80} {:>8} {:>14} {:>14}",
+        "Function", "Calls", "Self (us)", "Total (us)"
+    );
+    for record in records.iter().take(top) {
+        let name = if record.function.chars().count() > 80 {
+            let tail: String = record.function.chars().rev().take(77).collect();
+            format!("...{}", tail.chars().rev().collect::<String>())
+        } else {
+            record.function.clone()
+        };
+        println!(
+            "{:<80} {:>8} {:>14.3} {:>14.3}",
+            name, record.calls, record.self_us, record.total_us
+        );
+    }
+}
+
+/// Write the self/total table to a CSV file.
+pub fn write_function_csv(
+    records: &[FunctionRecord],
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    println!("\nWriting function table to CSV: {}", output_file);
+    let csv_file = File::create(output_file)?;
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(csv_file));
+
+    for record in records {
+        wtr.serialize(record)?;
+    }
+
+    wtr.flush()?;
+    println!(
+        "Successfully wrote {} records to {}",
+        records.len(),
+        output_file
+    );
+
+    Ok(())
+}
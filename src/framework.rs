@@ -0,0 +1,277 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::common::TraceEvent;
+use crate::decode_steps::FunctionMarker;
+
+/// Profiles shipped with the tool, see `profiles/`.
+const BUILTIN_PROFILES: &[&str] = &[
+    include_str!("../profiles/sglang.json"),
+    include_str!("../profiles/vllm.json"),
+    include_str!("../profiles/fastdeploy.json"),
+    include_str!("../profiles/trtllm.json"),
+    include_str!("../profiles/lmdeploy.json"),
+    include_str!("../profiles/tgi.json"),
+];
+
+/// How step latencies are derived from marker events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StepStrategy {
+    /// A step runs from one marker start to the next.
+    Interval,
+    /// Each marker event is one step; its `dur` is the latency.
+    Duration,
+}
+
+/// Unit of `ts`/`dur` of the marker events.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeUnit {
+    Ns,
+    #[default]
+    Us,
+    Ms,
+    S,
+}
+
+impl TimeUnit {
+    /// Convert a value in this unit to microseconds.
+    pub fn to_us(self, value: f64) -> f64 {
+        match self {
+            TimeUnit::Ns => value / 1000.0,
+            TimeUnit::Us => value,
+            TimeUnit::Ms => value * 1000.0,
+            TimeUnit::S => value * 1_000_000.0,
+        }
+    }
+}
+
+/// Which steps are discarded as warm-up or cool-down. Rules apply in field order.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WarmupRule {
+    /// Drop steps starting within this many ms of the first step.
+    #[serde(default)]
+    pub skip_start_ms: f64,
+    /// Drop steps ending within this many ms of the last step's end.
+    #[serde(default)]
+    pub skip_end_ms: f64,
+    /// Drop this many steps at the start.
+    #[serde(default)]
+    pub warmup_steps: usize,
+    /// Drop this many steps at the end.
+    #[serde(default)]
+    pub cooldown_steps: usize,
+    /// Keep only steps ending within this fraction of the remaining time range.
+    #[serde(default)]
+    pub keep_time_fraction: Option<f64>,
+    /// Keep the steady-state segment found by changepoint detection on the latency series.
+    #[serde(default)]
+    pub detect_steady_state: bool,
+}
+
+/// Marker definition as written in a profile file.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum MarkerSpec {
+    /// `<file path suffix>:<function>` of a python stack event, line number ignored.
+    Function(String),
+    /// Regex on the event name.
+    Regex(String),
+}
+
+/// Profile file layout.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileFile {
+    name: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    /// Only events of this category are markers (e.g. NVTX ranges).
+    #[serde(default)]
+    category: Option<String>,
+    marker: MarkerSpec,
+    strategy: StepStrategy,
+    #[serde(default)]
+    units: TimeUnit,
+    #[serde(default)]
+    warmup: WarmupRule,
+}
+
+/// How marker events are recognized by name.
+#[derive(Debug, Clone)]
+pub enum StepMatcher {
+    Function(FunctionMarker),
+    Name(Regex),
+}
+
+impl StepMatcher {
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            StepMatcher::Function(marker) => marker.matches(name),
+            StepMatcher::Name(pattern) => pattern.is_match(name),
+        }
+    }
+}
+
+impl fmt::Display for StepMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepMatcher::Function(marker) => write!(f, "function {}", marker),
+            StepMatcher::Name(pattern) => write!(f, "regex {}", pattern),
+        }
+    }
+}
+
+/// Step extraction rules of a serving framework.
+#[derive(Debug, Clone)]
+pub struct FrameworkProfile {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub category: Option<String>,
+    pub marker: StepMatcher,
+    pub strategy: StepStrategy,
+    pub units: TimeUnit,
+    pub warmup: WarmupRule,
+}
+
+impl FrameworkProfile {
+    /// Parse a profile from JSON text.
+    pub fn from_json(text: &str) -> Result<Self, Box<dyn Error>> {
+        let file: ProfileFile = serde_json::from_str(text)?;
+        let marker = match file.marker {
+            MarkerSpec::Function(spec) => StepMatcher::Function(FunctionMarker::parse(&spec)?),
+            MarkerSpec::Regex(pattern) => StepMatcher::Name(Regex::new(&pattern)?),
+        };
+        if let Some(fraction) = file.warmup.keep_time_fraction {
+            if !(fraction > 0.0 && fraction <= 1.0) {
+                return Err(format!(
+                    "Profile '{}': keep_time_fraction must be in (0, 1]",
+                    file.name
+                )
+                .into());
+            }
+        }
+        Ok(Self {
+            name: file.name,
+            aliases: file.aliases,
+            category: file.category,
+            marker,
+            strategy: file.strategy,
+            units: file.units,
+            warmup: file.warmup,
+        })
+    }
+
+    /// Load a profile from a JSON file. Other formats, such as TOML, are rejected.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let is_json = Path::new(path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        if !is_json {
+            return Err(format!(
+                "Unsupported profile file {}: profiles must be JSON with a .json extension",
+                path
+            )
+            .into());
+        }
+        let text = fs::read_to_string(path)?;
+        Self::from_json(&text).map_err(|e| format!("Invalid profile {}: {}", path, e).into())
+    }
+
+    /// All built-in profiles.
+    pub fn builtins() -> Vec<Self> {
+        BUILTIN_PROFILES
+            .iter()
+            .map(|text| Self::from_json(text).expect("built-in profile is valid"))
+            .collect()
+    }
+
+    /// Find a built-in profile by name or alias (case-insensitive).
+    pub fn builtin(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        Self::builtins()
+            .into_iter()
+            .find(|p| p.name == name || p.aliases.contains(&name))
+    }
+
+    /// Resolve a command-line framework argument: a built-in name or a profile file path.
+    pub fn resolve(arg: &str) -> Result<Self, Box<dyn Error>> {
+        if let Some(profile) = Self::builtin(arg) {
+            return Ok(profile);
+        }
+        if Path::new(arg).is_file() {
+            return Self::load(arg);
+        }
+        let names: Vec<String> = Self::builtins().into_iter().map(|p| p.name).collect();
+        Err(format!(
+            "Unknown framework '{}'. Supported: {}, or a profile JSON file",
+            arg,
+            names.join(", ")
+        )
+        .into())
+    }
+
+    /// Replace the marker with a python function, measured by intervals.
+    pub fn with_function_marker(mut self, marker: FunctionMarker) -> Self {
+        self.marker = StepMatcher::Function(marker);
+        self.strategy = StepStrategy::Interval;
+        self
+    }
+
+    /// Whether a complete event is a step marker of this framework.
+    pub fn matches(&self, event: &TraceEvent) -> bool {
+        event.ph.as_deref() == Some("X")
+            && self
+                .category
+                .as_ref()
+                .is_none_or(|cat| event.cat.as_deref() == Some(cat.as_str()))
+            && self.marker.matches(&event.name)
+    }
+}
+
+impl fmt::Display for FrameworkProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_steps::parse_steps;
+    use serde_json::Value;
+
+    /// Latencies (ms) of the steps a built-in profile finds in a fixture trace.
+    fn latencies(framework: &str, fixture: &str) -> Vec<f64> {
+        let profile = FrameworkProfile::builtin(framework).unwrap();
+        let json: Value = serde_json::from_str(fixture).unwrap();
+        parse_steps(json["traceEvents"].as_array().unwrap(), &profile)
+            .iter()
+            .map(|step| step.latency_ms)
+            .collect()
+    }
+
+    #[test]
+    fn trtllm_forward_step_intervals() {
+        let fixture = include_str!("../tests/fixtures/trtllm.json");
+        assert_eq!(latencies("tensorrt-llm", fixture), [12.0, 12.5, 13.0]);
+    }
+
+    #[test]
+    fn lmdeploy_model_forward_intervals() {
+        let fixture = include_str!("../tests/fixtures/lmdeploy.json");
+        assert_eq!(latencies("lmdeploy", fixture), [20.0, 21.0, 19.0]);
+    }
+
+    #[test]
+    fn tgi_generate_token_durations() {
+        let fixture = include_str!("../tests/fixtures/tgi.json");
+        assert_eq!(latencies("tgi", fixture), [25.0, 14.0, 14.5]);
+    }
+}
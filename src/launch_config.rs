@@ -0,0 +1,265 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::common::{load_trace_json, normalize_op_name, GpuOpKind, TraceEvent};
+
+/// Launch configuration report configuration.
+pub struct LaunchReportConfig {
+    pub input_file: String,
+    pub output_file: String,
+    /// Only report kernels whose name matches.
+    pub kernel_filter: Option<Regex>,
+    /// Only report kernels fully inside `[start, end]` (us).
+    pub time_window: Option<(f64, f64)>,
+}
+
+/// Output record: one row per kernel name and launch configuration.
+#[derive(Debug, Serialize)]
+pub struct LaunchConfigRecord {
+    pub kernel_name: String,
+    pub grid: String,
+    pub block: String,
+    pub registers_per_thread: String,
+    pub shared_memory: String,
+    pub count: usize,
+    pub total_duration_us: f64,
+    pub avg_duration_us: f64,
+    pub min_duration_us: f64,
+    pub max_duration_us: f64,
+    pub avg_occupancy_pct: Option<f64>,
+}
+
+/// Accumulated durations for one launch configuration.
+struct ConfigStats {
+    count: usize,
+    total: f64,
+    min: f64,
+    max: f64,
+    occupancy_total: f64,
+    occupancy_count: usize,
+}
+
+/// Format a launch dimension, e.g. `[128, 1, 1]` -> "128x1x1".
+fn format_launch_value(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::Array(dims)) => dims
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join("x"),
+        Some(Value::String(s)) => s.trim().to_string(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Read a numeric argument that may be encoded as a string.
+fn value_as_f64(value: Option<&Value>) -> Option<f64> {
+    let value = value?;
+    value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|s| s.trim().parse().ok()))
+}
+
+/// Group kernels by name and launch configuration and summarize their durations.
+pub fn analyze_launch_configs(
+    config: &LaunchReportConfig,
+) -> Result<Vec<LaunchConfigRecord>, Box<dyn Error>> {
+    let json = load_trace_json(&config.input_file)?;
+    let trace_events = json["traceEvents"]
+        .as_array()
+        .ok_or("traceEvents not found or not an array")?;
+
+    type ConfigKey = (String, String, String, String, String);
+    let mut groups: HashMap<ConfigKey, ConfigStats> = HashMap::new();
+    let mut kernel_count = 0;
+
+    for event_value in trace_events {
+        let event: TraceEvent = match TraceEvent::deserialize(event_value) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+
+        if event.ph.as_deref() != Some("X")
+            || event.cat.as_deref().and_then(GpuOpKind::from_category) != Some(GpuOpKind::Kernel)
+        {
+            continue;
+        }
+
+        let Some((start, end)) = event.time_range() else {
+            continue;
+        };
+        if let Some((window_start, window_end)) = config.time_window {
+            if start < window_start || end > window_end {
+                continue;
+            }
+        }
+
+        let name = normalize_op_name(&event.name);
+        if let Some(filter) = &config.kernel_filter {
+            if !filter.is_match(name) {
+                continue;
+            }
+        }
+
+        let Some(args) = &event.args else {
+            continue;
+        };
+        let key = (
+            name.to_string(),
+            format_launch_value(args.grid.as_ref()),
+            format_launch_value(args.block.as_ref()),
+            format_launch_value(args.registers_per_thread.as_ref()),
+            format_launch_value(args.shared_memory.as_ref()),
+        );
+
+        let duration = end - start;
+        let stats = groups.entry(key).or_insert(ConfigStats {
+            count: 0,
+            total: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            occupancy_total: 0.0,
+            occupancy_count: 0,
+        });
+        stats.count += 1;
+        stats.total += duration;
+        stats.min = stats.min.min(duration);
+        stats.max = stats.max.max(duration);
+        if let Some(occupancy) = value_as_f64(args.achieved_occupancy.as_ref()) {
+            stats.occupancy_total += occupancy;
+            stats.occupancy_count += 1;
+        }
+        kernel_count += 1;
+    }
+
+    println!(
+        "Found {} kernel launches in {} (kernel, launch configuration) groups",
+        kernel_count,
+        groups.len()
+    );
+
+    // Order kernels by total time, then configurations of each kernel by total time.
+    let mut kernel_totals: HashMap<String, f64> = HashMap::new();
+    for ((name, ..), stats) in &groups {
+        *kernel_totals.entry(name.clone()).or_insert(0.0) += stats.total;
+    }
+
+    let mut records: Vec<LaunchConfigRecord> = groups
+        .into_iter()
+        .map(
+            |((kernel_name, grid, block, registers, shared_memory), stats)| LaunchConfigRecord {
+                kernel_name,
+                grid,
+                block,
+                registers_per_thread: registers,
+                shared_memory,
+                count: stats.count,
+                total_duration_us: stats.total,
+                avg_duration_us: stats.total / stats.count as f64,
+                min_duration_us: stats.min,
+                max_duration_us: stats.max,
+                avg_occupancy_pct: (stats.occupancy_count > 0)
+                    .then(|| stats.occupancy_total / stats.occupancy_count as f64),
+            },
+        )
+        .collect();
+
+    records.sort_by(|a, b| {
+        kernel_totals[&b.kernel_name]
+            .partial_cmp(&kernel_totals[&a.kernel_name])
+            .unwrap()
+            .then_with(|| a.kernel_name.cmp(&b.kernel_name))
+            .then_with(|| {
+                b.total_duration_us
+                    .partial_cmp(&a.total_duration_us)
+                    .unwrap()
+            })
+    });
+
+    Ok(records)
+}
+
+/// Write launch configuration records to a CSV file.
+pub fn write_launch_config_csv(
+    records: &[LaunchConfigRecord],
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    println!("Writing launch configurations to CSV file: {}", output_file);
+    let csv_file = File::create(output_file)?;
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(csv_file));
+
+    for record in records {
+        wtr.serialize(record)?;
+    }
+
+    wtr.flush()?;
+    println!(
+        "Successfully wrote {} records to {}",
+        records.len(),
+        output_file
+    );
+
+    Ok(())
+}
+
+/// Print kernels launched with more than one configuration, in report order.
+pub fn print_launch_config_preview(records: &[LaunchConfigRecord], count: usize) {
+    let mut config_counts: HashMap<&str, usize> = HashMap::new();
+    for record in records {
+        *config_counts.entry(&record.kernel_name).or_insert(0) += 1;
+    }
+
+    let mut kernels: Vec<&str> = Vec::new();
+    for record in records {
+        if config_counts[record.kernel_name.as_str()] > 1
+            && !kernels.contains(&record.kernel_name.as_str())
+        {
+            kernels.push(&record.kernel_name);
+        }
+    }
+
+    if kernels.is_empty() {
+        println!("\nEvery kernel ran with a single launch configuration");
+        return;
+    }
+
+    println!(
+        "\n--- Kernels with multiple launch configurations (first {} of {}) ---",
+        count.min(kernels.len()),
+        kernels.len()
+    );
+    println!(
+        "{:<24} {:<16} {:>6} {:>10} {:>8} {:>12} {:>10}",
+        "Grid", "Block", "Regs", "SMem", "Count", "Avg(us)", "Occ(%)"
+    );
+    println!("{}", "-".repeat(92));
+
+    for kernel in kernels.iter().take(count) {
+        let name = if kernel.len() > 89 {
+            format!("{}...", &kernel[..89])
+        } else {
+            kernel.to_string()
+        };
+        println!("{}", name);
+        for record in records.iter().filter(|r| r.kernel_name == *kernel) {
+            println!(
+                "{:<24} {:<16} {:>6} {:>10} {:>8} {:>12.3} {:>10}",
+                record.grid,
+                record.block,
+                record.registers_per_thread,
+                record.shared_memory,
+                record.count,
+                record.avg_duration_us,
+                record
+                    .avg_occupancy_pct
+                    .map_or("-".to_string(), |o| format!("{:.1}", o))
+            );
+        }
+    }
+}
@@ -1,12 +1,47 @@
+mod batch;
+mod check;
 mod common;
+mod compare;
+mod counters;
+mod cpu_overhead;
+mod critical_path;
 mod decode_steps;
+mod drift;
 mod extractor;
+mod flamegraph;
+mod framework;
+mod launch_config;
+mod memcpy;
+mod memory;
+mod outliers;
+mod overlap;
+mod phase;
+mod plot;
 mod profile_stats;
+mod steady_state;
+mod summary;
 
-use decode_steps::{DecodeStepsConfig, Framework};
+use check::{print_check_table, run_check, CheckConfig};
+use compare::{compare_decode_latency, print_comparison, write_comparison_csv, CompareConfig};
+use counters::{analyze_counters, print_counter_summary, write_counter_csv, CounterConfig};
+use decode_steps::{DecodeStepsConfig, FunctionMarker};
 use extractor::{extract_kernels, print_preview, write_to_csv, ExtractConfig};
+use flamegraph::{export_flamegraph, print_function_table, write_function_csv, FlameGraphConfig};
+use framework::{FrameworkProfile, WarmupRule};
+use launch_config::{
+    analyze_launch_configs, print_launch_config_preview, write_launch_config_csv,
+    LaunchReportConfig,
+};
+use memcpy::MemcpyConfig;
+use memory::MemoryConfig;
+use phase::PhaseMode;
+use profile_stats::{KernelMarker, ProfileStatsConfig, StepMarker};
+use regex::Regex;
 use std::error::Error;
 
+/// Arguments of `decode-steps`, shared by the top-level usage and its error message.
+const DECODE_STEPS_ARGS: &str = "<framework> <input_json> [--output-csv <path>] [--min-ms <val>] [--max-ms <val>] [--auto-filter] [--marker <file>:<function>] [--warmup-steps <N>] [--cooldown-steps <N>] [--skip-start-ms <ms>] [--skip-end-ms <ms>] [--steady-state auto|off] [--cpu-breakdown] [--breakdown-csv <path>] [--drift] [--series-csv <path>] [--rolling-window <N>] [--batch-report] [--batch-csv <path>] [--batch-bucket <N>] [--histogram] [--bucket-ms <ms>] [--svg <path>] [--summary-json <path>] [--summary-yaml <path>]";
+
 fn print_usage(program: &str) {
     eprintln!("GPU Kernel Extractor - Extract and analyze GPU operations from trace files\n");
     eprintln!("Usage:");
@@ -16,25 +51,166 @@ fn print_usage(program: &str) {
     );
     eprintln!("      Extract GPU operations within a specific time range\n");
     eprintln!(
-        "  {} stats <input_json> <output_csv> [trim_start_kernel] [decode_max_duration_ms]",
+        "  {} stats <input_json> <output_csv> [trim_start_kernel] [decode_max_duration_ms] [options]",
         program
     );
     eprintln!("      Analyze ProfileStep GPU operations and calculate averages");
-    eprintln!("      trim_start_kernel: Optional kernel name to start counting from (default: recover_decode_task)");
+    eprintln!("      trim_start_kernel: Optional kernel marker to start counting from (default: recover_decode_task)");
     eprintln!("                         Use 'none' to disable trimming");
+    eprintln!(
+        "                         Marker format: [re:]<pattern>[@N] - substring or regex match,"
+    );
+    eprintln!("                         Nth occurrence (1-based, negative counts from the end)");
     eprintln!("      decode_max_duration_ms: Maximum duration threshold in ms for decode steps (default: 30)");
+    eprintln!("                              ProfileSteps exceeding this are filtered as prefill");
+    eprintln!("                              Use 'auto' to classify prefill/decode by duration and kernel signature");
+    eprintln!("                              and write prefill statistics to <output_csv stem>_prefill.csv");
     eprintln!(
-        "                              ProfileSteps exceeding this are filtered as prefill\n"
+        "      --trim-end:   Optional kernel marker of the last operation counted in each step"
     );
+    eprintln!("      --step-marker: How steps are recognized (default: cat:ProfileStep)");
     eprintln!(
-        "  {} decode-steps <framework> <input_json> [--output-csv <path>] [--min-ms <val>] [--max-ms <val>]",
-        program
+        "                     cat:<category> | name:<regex> | interval:<regex> (start to next start)"
     );
-    eprintln!("      Analyze decode step latency from sglang/vllm/fastdeploy traces");
-    eprintln!("      framework: sglang | vllm | fastdeploy");
+    eprintln!("      --detail-csv: Optional long-format CSV with one row per step and operation,");
+    eprintln!("                    including phase and skip reason");
+    eprintln!("      --outliers:   Number of slowest steps explained in the outlier report (default: 5, 0 disables)");
+    eprintln!("      --overlap-csv: Analyze exposed vs overlapped communication kernels per step");
+    eprintln!("      --critical-path-csv: Compute the cross-stream critical path of each step");
+    eprintln!(
+        "      --histogram:  Render duration histograms and CDFs of operations matching a regex"
+    );
+    eprintln!("      --bucket-us:  Histogram bucket width in us (default: automatic)");
+    eprintln!("      --svg:        Optional SVG output of the histograms (requires --histogram)\n");
+    eprintln!("  {} decode-steps {}", program, DECODE_STEPS_ARGS);
+    eprintln!("      Analyze decode step latency from serving framework traces");
+    eprintln!("      framework: sglang | vllm | fastdeploy | trtllm | lmdeploy | tgi,");
+    eprintln!("                 or a profile JSON file (see profiles/)");
     eprintln!("      --output-csv: Optional CSV output path for latencies");
     eprintln!("      --min-ms:     Minimum latency filter in ms (default: 10.0)");
-    eprintln!("      --max-ms:     Maximum latency filter in ms (default: 30.0)\n");
+    eprintln!("      --max-ms:     Maximum latency filter in ms (default: 30.0)");
+    eprintln!(
+        "      --auto-filter: Keep the decode latency cluster instead of using --min-ms/--max-ms"
+    );
+    eprintln!("      --marker:      Step-loop function as <file path suffix>:<function>, line number ignored");
+    eprintln!(
+        "                     (default: sglang/srt/managers/scheduler.py:get_next_batch_to_run,"
+    );
+    eprintln!("                      vllm/v1/engine/core.py:step_with_batch_queue; turns fastdeploy into interval mode)");
+    eprintln!("      --warmup-steps / --cooldown-steps: Drop this many steps at the start / end");
+    eprintln!("      --skip-start-ms / --skip-end-ms:   Drop steps within this time of the first step / last step end");
+    eprintln!("                     Any of these replaces the profile's warm-up rule");
+    eprintln!(
+        "      --steady-state: auto = keep the steady-state segment found by changepoint detection"
+    );
+    eprintln!("                      (default for interval-based profiles), off = disable it");
+    eprintln!("      --cpu-breakdown: Break each step down into host time by top-level function");
+    eprintln!("                       (scheduling, input prep, forward, sampling, output) vs GPU busy time");
+    eprintln!(
+        "      --breakdown-csv: Optional per-step CSV of the breakdown (implies --cpu-breakdown)"
+    );
+    eprintln!(
+        "      --drift:          Report the linear latency trend and changepoints over the run"
+    );
+    eprintln!("                        (warm-up rules and the latency filter apply; steady-state");
+    eprintln!(
+        "                        detection does not, so shifts outside the steady window show)"
+    );
+    eprintln!("      --series-csv:     Optional per-step latency series CSV with rolling mean and percentiles");
+    eprintln!("                        (implies --drift)");
+    eprintln!("      --rolling-window: Number of steps in the rolling statistics (default: 50)");
+    eprintln!("      --batch-report:   Annotate steps with batch size, running requests and token counts from");
+    eprintln!("                        event args or counter tracks; report latency by batch size");
+    eprintln!(
+        "      --batch-csv:      Optional per-step CSV of the annotations (implies --batch-report)"
+    );
+    eprintln!("      --batch-bucket:   Width of the batch size buckets (default: 1)");
+    eprintln!("      --histogram:      Render a latency histogram and CDF in the terminal");
+    eprintln!("      --bucket-ms:      Histogram bucket width in ms (default: automatic; implies --histogram)");
+    eprintln!("      --svg:            Optional SVG output of the histogram and CDF (implies --histogram)");
+    eprintln!(
+        "      --summary-json:   Write statistics, filter bounds, window and input file identity as JSON"
+    );
+    eprintln!("      --summary-yaml:   Same summary as YAML\n");
+    eprintln!(
+        "  {} launch-config <input_json> <output_csv> [--kernel <regex>] [--window <start_us,end_us>]",
+        program
+    );
+    eprintln!(
+        "      Group kernels by launch configuration (grid, block, registers, shared memory)"
+    );
+    eprintln!("      and report duration and achieved occupancy per configuration");
+    eprintln!("      --kernel: Only report kernels whose name matches the regex");
+    eprintln!("      --window: Only report kernels within the time range\n");
+    eprintln!(
+        "  {} memcpy <input_json> [--output-csv <path>] [--step-marker <spec>] [--decode-max-ms <val>]",
+        program
+    );
+    eprintln!("      Report memcpy bandwidth by direction and size, and flag pageable or");
+    eprintln!("      synchronous copies inside decode steps");
+    eprintln!("      --output-csv:    Optional CSV output path for per-transfer records");
+    eprintln!("      --step-marker:   How steps are recognized (default: cat:ProfileStep)");
+    eprintln!("      --decode-max-ms: Maximum duration in ms of decode steps (default: 30)\n");
+    eprintln!(
+        "  {} memory <input_json> [--output-csv <path>] [--step-marker <spec>]",
+        program
+    );
+    eprintln!(
+        "      Reconstruct allocated/reserved GPU memory over time from profiler memory events,"
+    );
+    eprintln!("      report the peak per device with the step and operator it occurred in");
+    eprintln!("      --output-csv:  Optional CSV output path for the memory timeline");
+    eprintln!("      --step-marker: How steps are recognized (default: cat:ProfileStep)\n");
+    eprintln!(
+        "  {} counters <input_json> [--output-csv <path>] [--counter <regex>] [--step-marker <spec>] [--window <start_us,end_us>]",
+        program
+    );
+    eprintln!("      Summarize counter tracks (ph = C) per step: samples, min, max, time-weighted average");
+    eprintln!("      --output-csv:  Optional CSV output path for per-window summaries");
+    eprintln!(
+        "      --counter:     Only report series (<event name>.<args key>) matching the regex"
+    );
+    eprintln!("      --step-marker: How steps are recognized (default: cat:ProfileStep)");
+    eprintln!("      --window:      Summarize a single time range instead of each step\n");
+    eprintln!(
+        "  {} flamegraph <input_json> <output_folded> [--table-csv <path>] [--window <start_us,end_us>] [--decode-steps <framework>] [--top <N>]",
+        program
+    );
+    eprintln!(
+        "      Rebuild per-thread call trees from nested host events and write folded stacks"
+    );
+    eprintln!("      (flamegraph.pl / inferno / speedscope input) plus a self/total time table");
+    eprintln!("      --table-csv:    Optional CSV output path for the self/total table");
+    eprintln!("      --window:       Only count time inside the time range");
+    eprintln!("      --decode-steps: Only count time inside decode steps detected for a framework or profile file");
+    eprintln!("      --top:          Number of functions printed (default: 20)\n");
+    eprintln!(
+        "  {} compare <baseline_framework> <baseline_json> <candidate_framework> <candidate_json> [--min-ms <val>] [--max-ms <val>] [--auto-filter] [--marker <file>:<function>] [--warmup-steps <N>] [--cooldown-steps <N>] [--skip-start-ms <ms>] [--skip-end-ms <ms>] [--steady-state auto|off] [--resamples <N>] [--confidence <level>] [--seed <N>] [--output-csv <path>]",
+        program
+    );
+    eprintln!("      Compare decode step latency of two traces: delta and change per statistic,");
+    eprintln!("      bootstrap confidence intervals and a Mann-Whitney U test");
+    eprintln!("      --min-ms / --max-ms / --auto-filter: Latency filter applied to both traces (as in decode-steps)");
+    eprintln!("      --marker / --warmup-steps / --cooldown-steps / --skip-start-ms / --skip-end-ms / --steady-state:");
+    eprintln!("                    Step marker and steady-state window of both traces (as in decode-steps)");
+    eprintln!("      --resamples:  Number of bootstrap resamples (default: 2000)");
+    eprintln!("      --confidence: Confidence level of the intervals and the test (default: 0.95)");
+    eprintln!("      --seed:       Seed of the resampling RNG (default: 42)");
+    eprintln!("      --output-csv: Optional CSV output path for the per-statistic comparison\n");
+    eprintln!(
+        "  {} check <framework> <input_json> <baseline_json> [--min-ms <val>] [--max-ms <val>] [--auto-filter] [--marker <file>:<function>] [--warmup-steps <N>] [--cooldown-steps <N>] [--skip-start-ms <ms>] [--skip-end-ms <ms>] [--steady-state auto|off] [--stats-csv <path>] [--update-baseline]",
+        program
+    );
+    eprintln!("      Regression gate: compare decode step statistics (and optionally per-kernel averages)");
+    eprintln!("      against a baseline JSON with per-metric tolerances; exits with status 2 on regression");
+    eprintln!("      --min-ms / --max-ms / --auto-filter: Latency filter (as in decode-steps)");
+    eprintln!("      --marker / --warmup-steps / --cooldown-steps / --skip-start-ms / --skip-end-ms / --steady-state:");
+    eprintln!("                         Step marker and steady-state window (as in decode-steps); the window used");
+    eprintln!("                         is printed with the table. Use --steady-state off to check the whole run");
+    eprintln!(
+        "      --stats-csv:       Output CSV of the stats command; checks its per-kernel averages"
+    );
+    eprintln!("      --update-baseline: Write the measured values into <baseline_json>, keeping its tolerances\n");
     eprintln!("Examples:");
     eprintln!(
         "  {} extract naive_spec_2.json output.csv 2684054.000,2687705.250",
@@ -45,6 +221,124 @@ fn print_usage(program: &str) {
         "  {} stats naive_spec_2.json profile_stats.csv none 50",
         program
     );
+    eprintln!(
+        "  {} stats naive_spec_2.json profile_stats.csv none auto",
+        program
+    );
+    eprintln!(
+        "  {} stats naive_spec_2.json profile_stats.csv 're:^embedding' 30 --trim-end 'MEMCPY_DtoH@-1'",
+        program
+    );
+}
+
+/// Parse a "start,end" time range in microseconds.
+fn parse_time_range(s: &str) -> Option<(f64, f64)> {
+    let time_range: Vec<f64> = s.split(',').filter_map(|s| s.parse::<f64>().ok()).collect();
+
+    match time_range[..] {
+        [start, end] => Some((start, end)),
+        _ => None,
+    }
+}
+
+/// Step marker and warm-up flags shared by `decode-steps`, `compare` and `check`.
+#[derive(Default)]
+struct WindowFlags {
+    marker: Option<FunctionMarker>,
+    warmup_steps: Option<usize>,
+    cooldown_steps: Option<usize>,
+    skip_start_ms: Option<f64>,
+    skip_end_ms: Option<f64>,
+    steady_state: Option<bool>,
+}
+
+impl WindowFlags {
+    /// Parse the flag at `args[*i]` and its value; returns false if it is not a window flag.
+    fn parse(&mut self, args: &[String], i: &mut usize) -> bool {
+        let flag = args[*i].clone();
+        match flag.as_str() {
+            "--marker" => {
+                *i += 1;
+                let spec = args.get(*i).unwrap_or_else(|| {
+                    eprintln!("Error: --marker requires a value");
+                    std::process::exit(1);
+                });
+                self.marker = Some(FunctionMarker::parse(spec).unwrap_or_else(|e| {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }));
+            }
+            "--warmup-steps" | "--cooldown-steps" => {
+                *i += 1;
+                let count = args
+                    .get(*i)
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_else(|| {
+                        eprintln!("Error: {} requires a non-negative integer", flag);
+                        std::process::exit(1);
+                    });
+                if flag == "--warmup-steps" {
+                    self.warmup_steps = Some(count);
+                } else {
+                    self.cooldown_steps = Some(count);
+                }
+            }
+            "--skip-start-ms" | "--skip-end-ms" => {
+                *i += 1;
+                let offset = args
+                    .get(*i)
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_else(|| {
+                        eprintln!("Error: {} requires a numeric value", flag);
+                        std::process::exit(1);
+                    });
+                if flag == "--skip-start-ms" {
+                    self.skip_start_ms = Some(offset);
+                } else {
+                    self.skip_end_ms = Some(offset);
+                }
+            }
+            "--steady-state" => {
+                *i += 1;
+                self.steady_state = match args.get(*i).map(String::as_str) {
+                    Some("auto") => Some(true),
+                    Some("off") => Some(false),
+                    _ => {
+                        eprintln!("Error: --steady-state requires 'auto' or 'off'");
+                        std::process::exit(1);
+                    }
+                };
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Apply the flags to a framework profile.
+    fn apply(&self, mut framework: FrameworkProfile) -> FrameworkProfile {
+        if let Some(ref marker) = self.marker {
+            framework = framework.with_function_marker(marker.clone());
+        }
+        // Explicit warm-up / cool-down bounds replace the profile's rule.
+        if self.warmup_steps.is_some()
+            || self.cooldown_steps.is_some()
+            || self.skip_start_ms.is_some()
+            || self.skip_end_ms.is_some()
+        {
+            framework.warmup = WarmupRule {
+                skip_start_ms: self.skip_start_ms.unwrap_or(0.0),
+                skip_end_ms: self.skip_end_ms.unwrap_or(0.0),
+                warmup_steps: self.warmup_steps.unwrap_or(0),
+                cooldown_steps: self.cooldown_steps.unwrap_or(0),
+                keep_time_fraction: None,
+                detect_steady_state: false,
+            };
+        }
+        if let Some(detect) = self.steady_state {
+            framework.warmup.detect_steady_state = detect;
+        }
+        framework
+    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -68,21 +362,16 @@ fn main() -> Result<(), Box<dyn Error>> {
                 std::process::exit(1);
             }
 
-            let time_range: Vec<f64> = args[4]
-                .split(',')
-                .filter_map(|s| s.parse::<f64>().ok())
-                .collect();
-
-            if time_range.len() != 2 {
+            let (start_time, end_time) = parse_time_range(&args[4]).unwrap_or_else(|| {
                 eprintln!("Invalid time range format. Expected: start,end");
                 std::process::exit(1);
-            }
+            });
 
             let config = ExtractConfig {
                 input_file: args[2].clone(),
                 output_file: args[3].clone(),
-                start_time: time_range[0],
-                end_time: time_range[1],
+                start_time,
+                end_time,
             };
 
             println!("Output CSV: {}", config.output_file);
@@ -92,9 +381,16 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
 
         "stats" => {
-            if args.len() < 4 || args.len() > 6 {
-                eprintln!("Error: 'stats' requires 2-4 arguments");
-                eprintln!("Usage: {} stats <input_json> <output_csv> [trim_start_kernel] [decode_max_duration_ms]", args[0]);
+            // Positional arguments come first, followed by optional flags.
+            let positional_end = args
+                .iter()
+                .skip(2)
+                .position(|a| a.starts_with("--"))
+                .map_or(args.len(), |pos| pos + 2);
+
+            if !(4..=6).contains(&positional_end) {
+                eprintln!("Error: 'stats' requires 2-4 positional arguments");
+                eprintln!("Usage: {} stats <input_json> <output_csv> [trim_start_kernel] [decode_max_duration_ms] [options]", args[0]);
                 std::process::exit(1);
             }
 
@@ -103,7 +399,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
             // Parse optional trim_start_kernel argument.
             // Defaults to "recover_decode_task"; pass "none" to disable trimming.
-            let trim_start_kernel: Option<&str> = if args.len() >= 5 {
+            let trim_start_kernel: Option<&str> = if positional_end >= 5 {
                 let kernel = args[4].as_str();
                 if kernel.eq_ignore_ascii_case("none") {
                     None
@@ -113,43 +409,165 @@ fn main() -> Result<(), Box<dyn Error>> {
             } else {
                 Some("recover_decode_task") // default
             };
+            let trim_start = trim_start_kernel
+                .map(KernelMarker::parse)
+                .transpose()
+                .unwrap_or_else(|e| {
+                    eprintln!("Error: Invalid trim_start_kernel: {}", e);
+                    std::process::exit(1);
+                });
 
-            // Parse optional decode_max_duration_ms argument (default: 30ms).
-            let decode_max_duration_ms: f64 = if args.len() == 6 {
-                args[5].parse().unwrap_or_else(|_| {
-                    eprintln!(
-                        "Warning: Invalid decode_max_duration_ms '{}', using default 30ms",
-                        args[5]
-                    );
-                    30.0
-                })
+            // Parse optional decode_max_duration_ms argument (default: 30ms, or 'auto').
+            let phase_mode = if positional_end == 6 {
+                if args[5].eq_ignore_ascii_case("auto") {
+                    PhaseMode::Auto
+                } else {
+                    PhaseMode::Threshold(args[5].parse().unwrap_or_else(|_| {
+                        eprintln!(
+                            "Warning: Invalid decode_max_duration_ms '{}', using default 30ms",
+                            args[5]
+                        );
+                        30.0
+                    }))
+                }
             } else {
-                30.0 // default
+                PhaseMode::Threshold(30.0) // default
             };
 
-            profile_stats::analyze_profile_stats(
-                input_file,
-                output_file,
-                trim_start_kernel,
-                decode_max_duration_ms,
-            )?;
+            // Parse optional flags.
+            let mut trim_end: Option<KernelMarker> = None;
+            let mut step_marker = StepMarker::default();
+            let mut detail_csv: Option<String> = None;
+            let mut outlier_count: usize = 5;
+            let mut overlap_csv: Option<String> = None;
+            let mut critical_path_csv: Option<String> = None;
+            let mut histogram: Option<Regex> = None;
+            let mut bucket_us: Option<f64> = None;
+            let mut svg: Option<String> = None;
+            let mut i = positional_end;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--trim-end" => {
+                        i += 1;
+                        let spec = args.get(i).unwrap_or_else(|| {
+                            eprintln!("Error: --trim-end requires a value");
+                            std::process::exit(1);
+                        });
+                        trim_end = Some(KernelMarker::parse(spec).unwrap_or_else(|e| {
+                            eprintln!("Error: Invalid --trim-end marker: {}", e);
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--detail-csv" => {
+                        i += 1;
+                        detail_csv = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --detail-csv requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--outliers" => {
+                        i += 1;
+                        outlier_count =
+                            args.get(i).and_then(|s| s.parse().ok()).unwrap_or_else(|| {
+                                eprintln!("Error: --outliers requires a non-negative integer");
+                                std::process::exit(1);
+                            });
+                    }
+                    "--critical-path-csv" => {
+                        i += 1;
+                        critical_path_csv = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --critical-path-csv requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--overlap-csv" => {
+                        i += 1;
+                        overlap_csv = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --overlap-csv requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--histogram" => {
+                        i += 1;
+                        histogram =
+                            Some(args.get(i).and_then(|s| Regex::new(s).ok()).unwrap_or_else(
+                                || {
+                                    eprintln!("Error: --histogram requires a valid regex");
+                                    std::process::exit(1);
+                                },
+                            ));
+                    }
+                    "--bucket-us" => {
+                        i += 1;
+                        bucket_us = Some(
+                            args.get(i)
+                                .and_then(|s| s.parse().ok())
+                                .filter(|&w: &f64| w > 0.0)
+                                .unwrap_or_else(|| {
+                                    eprintln!("Error: --bucket-us requires a positive number");
+                                    std::process::exit(1);
+                                }),
+                        );
+                    }
+                    "--svg" => {
+                        i += 1;
+                        svg = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --svg requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--step-marker" => {
+                        i += 1;
+                        let spec = args.get(i).unwrap_or_else(|| {
+                            eprintln!("Error: --step-marker requires a value");
+                            std::process::exit(1);
+                        });
+                        step_marker = StepMarker::parse(spec).unwrap_or_else(|e| {
+                            eprintln!("Error: {}", e);
+                            std::process::exit(1);
+                        });
+                    }
+                    other => {
+                        eprintln!("Unknown option: {}", other);
+                        std::process::exit(1);
+                    }
+                }
+                i += 1;
+            }
+
+            if svg.is_some() && histogram.is_none() {
+                eprintln!("Error: --svg requires --histogram <regex>");
+                std::process::exit(1);
+            }
+
+            let config = ProfileStatsConfig {
+                input_file: input_file.clone(),
+                output_file: output_file.clone(),
+                trim_start,
+                trim_end,
+                step_marker,
+                phase_mode,
+                detail_csv,
+                outlier_count,
+                overlap_csv,
+                critical_path_csv,
+                histogram,
+                bucket_us,
+                svg,
+            };
+
+            profile_stats::analyze_profile_stats(&config)?;
         }
 
         "decode-steps" => {
             if args.len() < 4 {
                 eprintln!("Error: 'decode-steps' requires at least 2 arguments");
-                eprintln!(
-                    "Usage: {} decode-steps <framework> <input_json> [--output-csv <path>] [--min-ms <val>] [--max-ms <val>]",
-                    args[0]
-                );
+                eprintln!("Usage: {} decode-steps {}", args[0], DECODE_STEPS_ARGS);
                 std::process::exit(1);
             }
 
-            let framework = Framework::from_str(&args[2]).unwrap_or_else(|| {
-                eprintln!(
-                    "Unknown framework '{}'. Supported: sglang, vllm, fastdeploy",
-                    args[2]
-                );
+            let framework = FrameworkProfile::resolve(&args[2]).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
                 std::process::exit(1);
             });
             let input_file = args[3].clone();
@@ -158,6 +576,21 @@ fn main() -> Result<(), Box<dyn Error>> {
             let mut output_csv: Option<String> = None;
             let mut min_ms: f64 = 10.0;
             let mut max_ms: f64 = 30.0;
+            let mut auto_filter = false;
+            let mut cpu_breakdown = false;
+            let mut breakdown_csv: Option<String> = None;
+            let mut window = WindowFlags::default();
+            let mut drift = false;
+            let mut series_csv: Option<String> = None;
+            let mut rolling_window: usize = 50;
+            let mut batch_report = false;
+            let mut batch_csv: Option<String> = None;
+            let mut batch_bucket: usize = 1;
+            let mut histogram = false;
+            let mut bucket_ms: Option<f64> = None;
+            let mut svg: Option<String> = None;
+            let mut summary_json: Option<String> = None;
+            let mut summary_yaml: Option<String> = None;
             let mut i = 4;
             while i < args.len() {
                 match args[i].as_str() {
@@ -188,25 +621,660 @@ fn main() -> Result<(), Box<dyn Error>> {
                                 std::process::exit(1);
                             });
                     }
+                    "--auto-filter" => {
+                        auto_filter = true;
+                    }
+                    "--cpu-breakdown" => {
+                        cpu_breakdown = true;
+                    }
+                    "--breakdown-csv" => {
+                        i += 1;
+                        cpu_breakdown = true;
+                        breakdown_csv = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --breakdown-csv requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--drift" => {
+                        drift = true;
+                    }
+                    "--series-csv" => {
+                        i += 1;
+                        drift = true;
+                        series_csv = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --series-csv requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--rolling-window" => {
+                        i += 1;
+                        rolling_window = args
+                            .get(i)
+                            .and_then(|s| s.parse().ok())
+                            .filter(|&n| n > 0)
+                            .unwrap_or_else(|| {
+                                eprintln!("Error: --rolling-window requires a positive integer");
+                                std::process::exit(1);
+                            });
+                    }
+                    "--batch-report" => {
+                        batch_report = true;
+                    }
+                    "--batch-csv" => {
+                        i += 1;
+                        batch_report = true;
+                        batch_csv = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --batch-csv requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--batch-bucket" => {
+                        i += 1;
+                        batch_bucket = args
+                            .get(i)
+                            .and_then(|s| s.parse().ok())
+                            .filter(|&n| n > 0)
+                            .unwrap_or_else(|| {
+                                eprintln!("Error: --batch-bucket requires a positive integer");
+                                std::process::exit(1);
+                            });
+                    }
+                    "--histogram" => {
+                        histogram = true;
+                    }
+                    "--bucket-ms" => {
+                        i += 1;
+                        histogram = true;
+                        bucket_ms = Some(
+                            args.get(i)
+                                .and_then(|s| s.parse().ok())
+                                .filter(|&w: &f64| w > 0.0)
+                                .unwrap_or_else(|| {
+                                    eprintln!("Error: --bucket-ms requires a positive number");
+                                    std::process::exit(1);
+                                }),
+                        );
+                    }
+                    "--svg" => {
+                        i += 1;
+                        histogram = true;
+                        svg = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --svg requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--summary-json" => {
+                        i += 1;
+                        summary_json = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --summary-json requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--summary-yaml" => {
+                        i += 1;
+                        summary_yaml = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --summary-yaml requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
                     other => {
-                        eprintln!("Unknown option: {}", other);
-                        std::process::exit(1);
+                        if !window.parse(&args, &mut i) {
+                            eprintln!("Unknown option: {}", other);
+                            std::process::exit(1);
+                        }
                     }
                 }
                 i += 1;
             }
 
             let config = DecodeStepsConfig {
-                framework,
+                framework: window.apply(framework),
                 input_file,
                 output_csv,
                 min_ms,
                 max_ms,
+                auto_filter,
+                cpu_breakdown,
+                breakdown_csv,
+                drift,
+                series_csv,
+                rolling_window,
+                batch_report,
+                batch_csv,
+                batch_bucket,
+                histogram,
+                bucket_ms,
+                svg,
+                summary_json,
+                summary_yaml,
             };
 
             decode_steps::analyze_decode_steps(&config)?;
         }
 
+        "launch-config" => {
+            if args.len() < 4 {
+                eprintln!("Error: 'launch-config' requires at least 2 arguments");
+                eprintln!(
+                    "Usage: {} launch-config <input_json> <output_csv> [--kernel <regex>] [--window <start_us,end_us>]",
+                    args[0]
+                );
+                std::process::exit(1);
+            }
+
+            // Parse optional flags.
+            let mut kernel_filter: Option<Regex> = None;
+            let mut time_window: Option<(f64, f64)> = None;
+            let mut i = 4;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--kernel" => {
+                        i += 1;
+                        kernel_filter =
+                            Some(args.get(i).and_then(|s| Regex::new(s).ok()).unwrap_or_else(
+                                || {
+                                    eprintln!("Error: --kernel requires a valid regex");
+                                    std::process::exit(1);
+                                },
+                            ));
+                    }
+                    "--window" => {
+                        i += 1;
+                        time_window = Some(
+                            args.get(i)
+                                .and_then(|s| parse_time_range(s))
+                                .unwrap_or_else(|| {
+                                    eprintln!("Error: --window requires <start_us,end_us>");
+                                    std::process::exit(1);
+                                }),
+                        );
+                    }
+                    other => {
+                        eprintln!("Unknown option: {}", other);
+                        std::process::exit(1);
+                    }
+                }
+                i += 1;
+            }
+
+            let config = LaunchReportConfig {
+                input_file: args[2].clone(),
+                output_file: args[3].clone(),
+                kernel_filter,
+                time_window,
+            };
+
+            let records = analyze_launch_configs(&config)?;
+            write_launch_config_csv(&records, &config.output_file)?;
+            print_launch_config_preview(&records, 10);
+        }
+
+        "memcpy" => {
+            if args.len() < 3 {
+                eprintln!("Error: 'memcpy' requires at least 1 argument");
+                eprintln!(
+                    "Usage: {} memcpy <input_json> [--output-csv <path>] [--step-marker <spec>] [--decode-max-ms <val>]",
+                    args[0]
+                );
+                std::process::exit(1);
+            }
+
+            // Parse optional flags.
+            let mut output_csv: Option<String> = None;
+            let mut step_marker = StepMarker::default();
+            let mut decode_max_duration_ms: f64 = 30.0;
+            let mut i = 3;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--output-csv" => {
+                        i += 1;
+                        output_csv = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --output-csv requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--step-marker" => {
+                        i += 1;
+                        let spec = args.get(i).unwrap_or_else(|| {
+                            eprintln!("Error: --step-marker requires a value");
+                            std::process::exit(1);
+                        });
+                        step_marker = StepMarker::parse(spec).unwrap_or_else(|e| {
+                            eprintln!("Error: {}", e);
+                            std::process::exit(1);
+                        });
+                    }
+                    "--decode-max-ms" => {
+                        i += 1;
+                        decode_max_duration_ms =
+                            args.get(i).and_then(|s| s.parse().ok()).unwrap_or_else(|| {
+                                eprintln!("Error: --decode-max-ms requires a numeric value");
+                                std::process::exit(1);
+                            });
+                    }
+                    other => {
+                        eprintln!("Unknown option: {}", other);
+                        std::process::exit(1);
+                    }
+                }
+                i += 1;
+            }
+
+            let config = MemcpyConfig {
+                input_file: args[2].clone(),
+                output_csv,
+                step_marker,
+                decode_max_duration_ms,
+            };
+
+            memcpy::analyze_memcpy(&config)?;
+        }
+
+        "memory" => {
+            if args.len() < 3 {
+                eprintln!("Error: 'memory' requires at least 1 argument");
+                eprintln!(
+                    "Usage: {} memory <input_json> [--output-csv <path>] [--step-marker <spec>]",
+                    args[0]
+                );
+                std::process::exit(1);
+            }
+
+            // Parse optional flags.
+            let mut output_csv: Option<String> = None;
+            let mut step_marker = StepMarker::default();
+            let mut i = 3;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--output-csv" => {
+                        i += 1;
+                        output_csv = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --output-csv requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--step-marker" => {
+                        i += 1;
+                        let spec = args.get(i).unwrap_or_else(|| {
+                            eprintln!("Error: --step-marker requires a value");
+                            std::process::exit(1);
+                        });
+                        step_marker = StepMarker::parse(spec).unwrap_or_else(|e| {
+                            eprintln!("Error: {}", e);
+                            std::process::exit(1);
+                        });
+                    }
+                    other => {
+                        eprintln!("Unknown option: {}", other);
+                        std::process::exit(1);
+                    }
+                }
+                i += 1;
+            }
+
+            let config = MemoryConfig {
+                input_file: args[2].clone(),
+                output_csv,
+                step_marker,
+            };
+
+            memory::analyze_memory(&config)?;
+        }
+
+        "counters" => {
+            if args.len() < 3 {
+                eprintln!("Error: 'counters' requires at least 1 argument");
+                eprintln!(
+                    "Usage: {} counters <input_json> [--output-csv <path>] [--counter <regex>] [--step-marker <spec>] [--window <start_us,end_us>]",
+                    args[0]
+                );
+                std::process::exit(1);
+            }
+
+            // Parse optional flags.
+            let mut output_csv: Option<String> = None;
+            let mut counter_filter: Option<Regex> = None;
+            let mut step_marker = StepMarker::default();
+            let mut time_window: Option<(f64, f64)> = None;
+            let mut i = 3;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--output-csv" => {
+                        i += 1;
+                        output_csv = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --output-csv requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--counter" => {
+                        i += 1;
+                        counter_filter =
+                            Some(args.get(i).and_then(|s| Regex::new(s).ok()).unwrap_or_else(
+                                || {
+                                    eprintln!("Error: --counter requires a valid regex");
+                                    std::process::exit(1);
+                                },
+                            ));
+                    }
+                    "--step-marker" => {
+                        i += 1;
+                        let spec = args.get(i).unwrap_or_else(|| {
+                            eprintln!("Error: --step-marker requires a value");
+                            std::process::exit(1);
+                        });
+                        step_marker = StepMarker::parse(spec).unwrap_or_else(|e| {
+                            eprintln!("Error: {}", e);
+                            std::process::exit(1);
+                        });
+                    }
+                    "--window" => {
+                        i += 1;
+                        time_window = Some(
+                            args.get(i)
+                                .and_then(|s| parse_time_range(s))
+                                .unwrap_or_else(|| {
+                                    eprintln!("Error: --window requires <start_us,end_us>");
+                                    std::process::exit(1);
+                                }),
+                        );
+                    }
+                    other => {
+                        eprintln!("Unknown option: {}", other);
+                        std::process::exit(1);
+                    }
+                }
+                i += 1;
+            }
+
+            let config = CounterConfig {
+                input_file: args[2].clone(),
+                output_csv,
+                counter_filter,
+                step_marker,
+                time_window,
+            };
+
+            let records = analyze_counters(&config)?;
+            print_counter_summary(&records);
+            if let Some(ref csv_path) = config.output_csv {
+                write_counter_csv(&records, csv_path)?;
+            }
+        }
+
+        "flamegraph" => {
+            if args.len() < 4 {
+                eprintln!("Error: 'flamegraph' requires at least 2 arguments");
+                eprintln!(
+                    "Usage: {} flamegraph <input_json> <output_folded> [--table-csv <path>] [--window <start_us,end_us>] [--decode-steps <framework>] [--top <N>]",
+                    args[0]
+                );
+                std::process::exit(1);
+            }
+
+            // Parse optional flags.
+            let mut table_csv: Option<String> = None;
+            let mut time_window: Option<(f64, f64)> = None;
+            let mut decode_framework: Option<FrameworkProfile> = None;
+            let mut top: usize = 20;
+            let mut i = 4;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--table-csv" => {
+                        i += 1;
+                        table_csv = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --table-csv requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--window" => {
+                        i += 1;
+                        time_window = Some(
+                            args.get(i)
+                                .and_then(|s| parse_time_range(s))
+                                .unwrap_or_else(|| {
+                                    eprintln!("Error: --window requires <start_us,end_us>");
+                                    std::process::exit(1);
+                                }),
+                        );
+                    }
+                    "--decode-steps" => {
+                        i += 1;
+                        let spec = args.get(i).unwrap_or_else(|| {
+                            eprintln!("Error: --decode-steps requires a framework or profile file");
+                            std::process::exit(1);
+                        });
+                        decode_framework =
+                            Some(FrameworkProfile::resolve(spec).unwrap_or_else(|e| {
+                                eprintln!("Error: {}", e);
+                                std::process::exit(1);
+                            }));
+                    }
+                    "--top" => {
+                        i += 1;
+                        top = args.get(i).and_then(|s| s.parse().ok()).unwrap_or_else(|| {
+                            eprintln!("Error: --top requires a non-negative integer");
+                            std::process::exit(1);
+                        });
+                    }
+                    other => {
+                        eprintln!("Unknown option: {}", other);
+                        std::process::exit(1);
+                    }
+                }
+                i += 1;
+            }
+
+            let config = FlameGraphConfig {
+                input_file: args[2].clone(),
+                output_folded: args[3].clone(),
+                table_csv,
+                time_window,
+                decode_framework,
+                top,
+            };
+
+            let records = export_flamegraph(&config)?;
+            print_function_table(&records, config.top);
+            if let Some(ref csv_path) = config.table_csv {
+                write_function_csv(&records, csv_path)?;
+            }
+        }
+
+        "compare" => {
+            if args.len() < 6 {
+                eprintln!("Error: 'compare' requires at least 4 arguments");
+                eprintln!(
+                    "Usage: {} compare <baseline_framework> <baseline_json> <candidate_framework> <candidate_json> [--min-ms <val>] [--max-ms <val>] [--auto-filter] [--marker <file>:<function>] [--warmup-steps <N>] [--cooldown-steps <N>] [--skip-start-ms <ms>] [--skip-end-ms <ms>] [--steady-state auto|off] [--resamples <N>] [--confidence <level>] [--seed <N>] [--output-csv <path>]",
+                    args[0]
+                );
+                std::process::exit(1);
+            }
+
+            let resolve = |arg: &str| {
+                FrameworkProfile::resolve(arg).unwrap_or_else(|e| {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                })
+            };
+            let baseline_framework = resolve(&args[2]);
+            let candidate_framework = resolve(&args[4]);
+
+            // Parse optional flags.
+            let mut min_ms: f64 = 10.0;
+            let mut max_ms: f64 = 30.0;
+            let mut auto_filter = false;
+            let mut resamples: usize = 2000;
+            let mut confidence: f64 = 0.95;
+            let mut seed: u64 = 42;
+            let mut output_csv: Option<String> = None;
+            let mut window = WindowFlags::default();
+            let mut i = 6;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--min-ms" => {
+                        i += 1;
+                        min_ms = args.get(i).and_then(|s| s.parse().ok()).unwrap_or_else(|| {
+                            eprintln!("Error: --min-ms requires a numeric value");
+                            std::process::exit(1);
+                        });
+                    }
+                    "--max-ms" => {
+                        i += 1;
+                        max_ms = args.get(i).and_then(|s| s.parse().ok()).unwrap_or_else(|| {
+                            eprintln!("Error: --max-ms requires a numeric value");
+                            std::process::exit(1);
+                        });
+                    }
+                    "--auto-filter" => {
+                        auto_filter = true;
+                    }
+                    "--resamples" => {
+                        i += 1;
+                        resamples = args
+                            .get(i)
+                            .and_then(|s| s.parse().ok())
+                            .filter(|&n| n > 0)
+                            .unwrap_or_else(|| {
+                                eprintln!("Error: --resamples requires a positive integer");
+                                std::process::exit(1);
+                            });
+                    }
+                    "--confidence" => {
+                        i += 1;
+                        confidence = args
+                            .get(i)
+                            .and_then(|s| s.parse().ok())
+                            .filter(|&c: &f64| c > 0.0 && c < 1.0)
+                            .unwrap_or_else(|| {
+                                eprintln!("Error: --confidence requires a value in (0, 1)");
+                                std::process::exit(1);
+                            });
+                    }
+                    "--seed" => {
+                        i += 1;
+                        seed = args.get(i).and_then(|s| s.parse().ok()).unwrap_or_else(|| {
+                            eprintln!("Error: --seed requires a non-negative integer");
+                            std::process::exit(1);
+                        });
+                    }
+                    "--output-csv" => {
+                        i += 1;
+                        output_csv = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --output-csv requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    other => {
+                        if !window.parse(&args, &mut i) {
+                            eprintln!("Unknown option: {}", other);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                i += 1;
+            }
+
+            let config = CompareConfig {
+                baseline_framework: window.apply(baseline_framework),
+                baseline_file: args[3].clone(),
+                candidate_framework: window.apply(candidate_framework),
+                candidate_file: args[5].clone(),
+                min_ms,
+                max_ms,
+                auto_filter,
+                resamples,
+                confidence,
+                seed,
+                output_csv,
+            };
+
+            let comparison = compare_decode_latency(&config)?;
+            print_comparison(&comparison);
+            if let Some(ref csv_path) = config.output_csv {
+                write_comparison_csv(&comparison, csv_path)?;
+            }
+        }
+
+        "check" => {
+            if args.len() < 5 {
+                eprintln!("Error: 'check' requires at least 3 arguments");
+                eprintln!(
+                    "Usage: {} check <framework> <input_json> <baseline_json> [--min-ms <val>] [--max-ms <val>] [--auto-filter] [--marker <file>:<function>] [--warmup-steps <N>] [--cooldown-steps <N>] [--skip-start-ms <ms>] [--skip-end-ms <ms>] [--steady-state auto|off] [--stats-csv <path>] [--update-baseline]",
+                    args[0]
+                );
+                std::process::exit(1);
+            }
+
+            let framework = FrameworkProfile::resolve(&args[2]).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+
+            // Parse optional flags.
+            let mut min_ms: f64 = 10.0;
+            let mut max_ms: f64 = 30.0;
+            let mut auto_filter = false;
+            let mut window = WindowFlags::default();
+            let mut stats_csv: Option<String> = None;
+            let mut update_baseline = false;
+            let mut i = 5;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--min-ms" => {
+                        i += 1;
+                        min_ms = args.get(i).and_then(|s| s.parse().ok()).unwrap_or_else(|| {
+                            eprintln!("Error: --min-ms requires a numeric value");
+                            std::process::exit(1);
+                        });
+                    }
+                    "--max-ms" => {
+                        i += 1;
+                        max_ms = args.get(i).and_then(|s| s.parse().ok()).unwrap_or_else(|| {
+                            eprintln!("Error: --max-ms requires a numeric value");
+                            std::process::exit(1);
+                        });
+                    }
+                    "--auto-filter" => {
+                        auto_filter = true;
+                    }
+                    "--stats-csv" => {
+                        i += 1;
+                        stats_csv = Some(args.get(i).cloned().unwrap_or_else(|| {
+                            eprintln!("Error: --stats-csv requires a value");
+                            std::process::exit(1);
+                        }));
+                    }
+                    "--update-baseline" => {
+                        update_baseline = true;
+                    }
+                    other => {
+                        if !window.parse(&args, &mut i) {
+                            eprintln!("Unknown option: {}", other);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                i += 1;
+            }
+
+            let config = CheckConfig {
+                framework: window.apply(framework),
+                input_file: args[3].clone(),
+                baseline_file: args[4].clone(),
+                min_ms,
+                max_ms,
+                auto_filter,
+                stats_csv,
+                update_baseline,
+            };
+
+            let result = run_check(&config)?;
+            // Status 1 is used for usage and input errors.
+            if !config.update_baseline && !print_check_table(&result) {
+                std::process::exit(2);
+            }
+        }
+
         _ => {
             eprintln!("Unknown command: {}", command);
             print_usage(&args[0]);
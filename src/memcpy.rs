@@ -0,0 +1,301 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::common::{load_trace_json, value_as_i64, GpuOpKind, TraceEvent};
+use crate::profile_stats::{ProfileStep, StepMarker};
+
+/// Upper bounds (exclusive) of the transfer size buckets, with their labels.
+const SIZE_BUCKETS: &[(i64, &str)] = &[
+    (4 << 10, "<4KB"),
+    (64 << 10, "4KB-64KB"),
+    (1 << 20, "64KB-1MB"),
+    (16 << 20, "1MB-16MB"),
+    (i64::MAX, ">=16MB"),
+];
+
+/// Memcpy analysis configuration.
+pub struct MemcpyConfig {
+    pub input_file: String,
+    pub output_csv: Option<String>,
+    /// How step boundaries are recognized.
+    pub step_marker: StepMarker,
+    /// Steps up to this duration (ms) are decode steps.
+    pub decode_max_duration_ms: f64,
+}
+
+/// Per-transfer memcpy record.
+#[derive(Debug, Serialize)]
+pub struct MemcpyRecord {
+    pub name: String,
+    pub direction: String,
+    pub size_bucket: String,
+    pub bytes: Option<i64>,
+    pub start_time_us: f64,
+    pub duration_us: f64,
+    pub bandwidth_gbps: Option<f64>,
+    pub pageable: bool,
+    /// Issued by a synchronous runtime call, or implicitly synchronous (pageable memory).
+    pub synchronous: bool,
+    /// Decode step containing the transfer, if any.
+    pub decode_step: Option<String>,
+}
+
+/// Aggregated bandwidth per direction and size bucket.
+#[derive(Default)]
+struct BucketStats {
+    count: usize,
+    total_bytes: i64,
+    total_duration: f64,
+    min_bandwidth: Option<f64>,
+    max_bandwidth: Option<f64>,
+}
+
+/// Copy direction from the event name, e.g. "MEMCPY_DtoH" or "Memcpy HtoD (Pinned -> Device)".
+fn memcpy_direction(name: &str) -> &'static str {
+    let lower = name.to_lowercase();
+    ["HtoD", "DtoH", "DtoD", "PtoP", "HtoH"]
+        .into_iter()
+        .find(|dir| lower.contains(&dir.to_lowercase()))
+        .unwrap_or("unknown")
+}
+
+fn size_bucket(bytes: Option<i64>) -> &'static str {
+    match bytes {
+        Some(b) => SIZE_BUCKETS
+            .iter()
+            .find(|(limit, _)| b < *limit)
+            .map_or("unknown", |(_, label)| label),
+        None => "unknown",
+    }
+}
+
+/// Analyze memcpy transfers: bandwidth per transfer, aggregated by direction and size,
+/// with pageable or synchronous copies inside decode steps flagged.
+pub fn analyze_memcpy(config: &MemcpyConfig) -> Result<(), Box<dyn Error>> {
+    let json = load_trace_json(&config.input_file)?;
+    let trace_events = json["traceEvents"]
+        .as_array()
+        .ok_or("traceEvents not found or not an array")?;
+
+    let mut marker_events: Vec<ProfileStep> = Vec::new();
+    let mut sync_correlations: HashSet<i64> = HashSet::new();
+    let mut records: Vec<MemcpyRecord> = Vec::new();
+    let mut record_correlations: Vec<Option<i64>> = Vec::new();
+
+    for event_value in trace_events {
+        let event: TraceEvent = match TraceEvent::deserialize(event_value) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+
+        if event.ph.as_deref() != Some("X") {
+            continue;
+        }
+        let Some((start, end)) = event.time_range() else {
+            continue;
+        };
+
+        if config.step_marker.matches(&event) {
+            marker_events.push(ProfileStep {
+                name: event.name.clone(),
+                start_time: start,
+                end_time: end,
+            });
+        }
+
+        let correlation = event
+            .args
+            .as_ref()
+            .and_then(|args| args.correlation.as_ref())
+            .and_then(value_as_i64);
+
+        // Host-side runtime calls: cudaMemcpy without "Async" blocks the host.
+        if event.name.starts_with("cudaMemcpy") && !event.name.contains("Async") {
+            sync_correlations.extend(correlation);
+            continue;
+        }
+
+        if event.cat.as_deref().and_then(GpuOpKind::from_category) != Some(GpuOpKind::Memcpy) {
+            continue;
+        }
+
+        let args = event.args.as_ref();
+        let bytes = args.and_then(|a| a.bytes.as_ref()).and_then(value_as_i64);
+        let duration = end - start;
+        let pageable = event.name.to_lowercase().contains("pageable");
+
+        records.push(MemcpyRecord {
+            name: event.name.clone(),
+            direction: memcpy_direction(&event.name).to_string(),
+            size_bucket: size_bucket(bytes).to_string(),
+            bytes,
+            start_time_us: start,
+            duration_us: duration,
+            // bytes / us = MB/s; divide by 1e3 for GB/s.
+            bandwidth_gbps: bytes
+                .filter(|_| duration > 0.0)
+                .map(|b| b as f64 / duration / 1e3),
+            pageable,
+            synchronous: pageable,
+            decode_step: None,
+        });
+        record_correlations.push(correlation);
+    }
+
+    for (record, correlation) in records.iter_mut().zip(&record_correlations) {
+        if correlation.is_some_and(|c| sync_correlations.contains(&c)) {
+            record.synchronous = true;
+        }
+    }
+
+    records.sort_by(|a, b| a.start_time_us.partial_cmp(&b.start_time_us).unwrap());
+
+    // Attach transfers to the decode step containing them.
+    let decode_max_duration_us = config.decode_max_duration_ms * 1000.0;
+    let decode_steps: Vec<ProfileStep> = config
+        .step_marker
+        .build_steps(marker_events)
+        .into_iter()
+        .filter(|step| step.end_time - step.start_time <= decode_max_duration_us)
+        .collect();
+    for step in &decode_steps {
+        let first = records.partition_point(|r| r.start_time_us < step.start_time);
+        for record in records[first..]
+            .iter_mut()
+            .take_while(|r| r.start_time_us <= step.end_time)
+        {
+            record.decode_step = Some(step.name.clone());
+        }
+    }
+
+    println!(
+        "Found {} memcpy transfers, {} decode steps (duration <= {}ms, marker '{}')",
+        records.len(),
+        decode_steps.len(),
+        config.decode_max_duration_ms,
+        config.step_marker
+    );
+
+    print_bandwidth_summary(&records);
+    print_flagged_copies(&records, 10);
+
+    if let Some(ref csv_path) = config.output_csv {
+        write_memcpy_csv(&records, csv_path)?;
+    }
+
+    Ok(())
+}
+
+/// Print aggregated bandwidth per direction and size bucket.
+fn print_bandwidth_summary(records: &[MemcpyRecord]) {
+    let mut buckets: BTreeMap<(&str, usize), BucketStats> = BTreeMap::new();
+    for record in records {
+        let bucket_idx = SIZE_BUCKETS
+            .iter()
+            .position(|(_, label)| *label == record.size_bucket)
+            .unwrap_or(SIZE_BUCKETS.len());
+        let stats = buckets
+            .entry((record.direction.as_str(), bucket_idx))
+            .or_default();
+        stats.count += 1;
+        stats.total_duration += record.duration_us;
+        if let (Some(bytes), Some(bw)) = (record.bytes, record.bandwidth_gbps) {
+            stats.total_bytes += bytes;
+            stats.min_bandwidth = Some(stats.min_bandwidth.map_or(bw, |m| m.min(bw)));
+            stats.max_bandwidth = Some(stats.max_bandwidth.map_or(bw, |m| m.max(bw)));
+        }
+    }
+
+    println!("\n--- Memcpy bandwidth by direction and size ---");
+    println!(
+        "{:<8} {:<10} {:>8} {:>14} {:>14} {:>10} {:>10} {:>10}",
+        "Dir", "Size", "Count", "Bytes", "Time(us)", "Avg(GB/s)", "Min(GB/s)", "Max(GB/s)"
+    );
+    println!("{}", "-".repeat(91));
+
+    let format_bw = |bw: Option<f64>| bw.map_or("-".to_string(), |b| format!("{:.3}", b));
+    for ((direction, bucket_idx), stats) in &buckets {
+        let label = SIZE_BUCKETS
+            .get(*bucket_idx)
+            .map_or("unknown", |(_, label)| label);
+        let avg = (stats.total_bytes > 0 && stats.total_duration > 0.0)
+            .then(|| stats.total_bytes as f64 / stats.total_duration / 1e3);
+        println!(
+            "{:<8} {:<10} {:>8} {:>14} {:>14.3} {:>10} {:>10} {:>10}",
+            direction,
+            label,
+            stats.count,
+            stats.total_bytes,
+            stats.total_duration,
+            format_bw(avg),
+            format_bw(stats.min_bandwidth),
+            format_bw(stats.max_bandwidth)
+        );
+    }
+}
+
+/// Print pageable or synchronous copies that happen inside decode steps.
+fn print_flagged_copies(records: &[MemcpyRecord], count: usize) {
+    let mut flagged: Vec<&MemcpyRecord> = records
+        .iter()
+        .filter(|r| r.decode_step.is_some() && (r.pageable || r.synchronous))
+        .collect();
+
+    if flagged.is_empty() {
+        println!("\nNo pageable or synchronous copies inside decode steps");
+        return;
+    }
+
+    let steps: HashSet<&str> = flagged
+        .iter()
+        .filter_map(|r| r.decode_step.as_deref())
+        .collect();
+    println!(
+        "\n--- Pageable / synchronous copies inside decode steps: {} copies in {} steps ---",
+        flagged.len(),
+        steps.len()
+    );
+
+    flagged.sort_by(|a, b| b.duration_us.partial_cmp(&a.duration_us).unwrap());
+    for (i, record) in flagged.iter().take(count).enumerate() {
+        let mut reasons: Vec<&str> = Vec::new();
+        if record.pageable {
+            reasons.push("pageable");
+        }
+        if record.synchronous {
+            reasons.push("synchronous");
+        }
+        println!(
+            "{}. {} | {} | {:.3} us | {} bytes | {}",
+            i + 1,
+            record.decode_step.as_deref().unwrap_or(""),
+            record.name,
+            record.duration_us,
+            record.bytes.map_or("?".to_string(), |b| b.to_string()),
+            reasons.join(", ")
+        );
+    }
+}
+
+/// Write per-transfer memcpy records to a CSV file.
+fn write_memcpy_csv(records: &[MemcpyRecord], output_file: &str) -> Result<(), Box<dyn Error>> {
+    println!("Writing memcpy transfers to CSV: {}", output_file);
+    let csv_file = File::create(output_file)?;
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(csv_file));
+
+    for record in records {
+        wtr.serialize(record)?;
+    }
+
+    wtr.flush()?;
+    println!(
+        "Successfully wrote {} records to {}",
+        records.len(),
+        output_file
+    );
+
+    Ok(())
+}
@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::common::{load_trace_json, GpuOpKind, TraceEvent};
+use crate::counters::numeric_args;
+use crate::profile_stats::{ProfileStep, StepMarker};
+
+/// Memory analysis configuration.
+pub struct MemoryConfig {
+    pub input_file: String,
+    pub output_csv: Option<String>,
+    /// How step boundaries are recognized when locating the peak.
+    pub step_marker: StepMarker,
+}
+
+/// One point of the memory timeline (values carried forward from earlier samples).
+#[derive(Debug, Clone, Serialize)]
+pub struct MemorySample {
+    pub time_us: f64,
+    pub device: i64,
+    pub allocated_bytes: Option<f64>,
+    pub reserved_bytes: Option<f64>,
+}
+
+/// A CPU-side span that may contain a memory peak.
+struct HostSpan {
+    name: String,
+    tid: Option<Value>,
+    start: f64,
+    end: f64,
+}
+
+/// Look up a numeric argument by case-insensitive key.
+fn arg_number(args: &Map<String, Value>, keys: &[&str]) -> Option<f64> {
+    args.iter()
+        .find(|(k, _)| keys.iter().any(|key| k.eq_ignore_ascii_case(key)))
+        .and_then(|(_, v)| {
+            v.as_f64()
+                .or_else(|| v.as_str().and_then(|s| s.trim().parse().ok()))
+        })
+}
+
+/// Device id of a memory event: `Device Id`, `device`, or the index in a Paddle place
+/// string such as "Place(gpu:0)".
+fn memory_device(args: &Map<String, Value>) -> i64 {
+    if let Some(id) = arg_number(args, &["Device Id", "device"]) {
+        return id as i64;
+    }
+    args.iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("place"))
+        .and_then(|(_, v)| v.as_str())
+        .and_then(|place| place.rsplit(':').next())
+        .and_then(|id| id.trim_end_matches(')').parse().ok())
+        .unwrap_or(0)
+}
+
+/// Parse a memory sample from a PyTorch/Paddle `[memory]` instant event or a memory counter event.
+fn parse_memory_sample(event: &TraceEvent, args: &Map<String, Value>) -> Option<MemorySample> {
+    let ts = event.ts?;
+    let (allocated, reserved) = match event.ph.as_deref() {
+        Some("i") | Some("I") if event.name == "[memory]" => (
+            arg_number(args, &["Total Allocated", "Current allocated"]),
+            arg_number(args, &["Total Reserved", "Current reserved"]),
+        ),
+        Some("C") => {
+            let find = |needle: &str| {
+                numeric_args(args)
+                    .find(|(k, _)| k.to_lowercase().contains(needle))
+                    .map(|(_, v)| v)
+            };
+            (find("allocated"), find("reserved"))
+        }
+        _ => return None,
+    };
+
+    if allocated.is_none() && reserved.is_none() {
+        return None;
+    }
+
+    let device = match event.ph.as_deref() {
+        Some("C") => event.device().unwrap_or(0),
+        _ => memory_device(args),
+    };
+
+    Some(MemorySample {
+        time_us: ts,
+        device,
+        allocated_bytes: allocated,
+        reserved_bytes: reserved,
+    })
+}
+
+/// Reconstruct allocated/reserved memory over time per device, report the peaks with the
+/// step and operator they fall in, and optionally export the timeline.
+pub fn analyze_memory(config: &MemoryConfig) -> Result<(), Box<dyn Error>> {
+    let json = load_trace_json(&config.input_file)?;
+    let trace_events = json["traceEvents"]
+        .as_array()
+        .ok_or("traceEvents not found or not an array")?;
+
+    let mut samples: Vec<MemorySample> = Vec::new();
+    let mut marker_events: Vec<ProfileStep> = Vec::new();
+    let mut host_spans: Vec<HostSpan> = Vec::new();
+    let empty_args = Map::new();
+
+    for event_value in trace_events {
+        let event: TraceEvent = match TraceEvent::deserialize(event_value) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+
+        if event.ph.as_deref() == Some("X") {
+            if let Some((start, end)) = event.time_range() {
+                if config.step_marker.matches(&event) {
+                    marker_events.push(ProfileStep {
+                        name: event.name.clone(),
+                        start_time: start,
+                        end_time: end,
+                    });
+                } else if event
+                    .cat
+                    .as_deref()
+                    .and_then(GpuOpKind::from_category)
+                    .is_none()
+                {
+                    host_spans.push(HostSpan {
+                        name: event.name.clone(),
+                        tid: event.tid.clone(),
+                        start,
+                        end,
+                    });
+                }
+            }
+            continue;
+        }
+
+        let args = event_value["args"].as_object().unwrap_or(&empty_args);
+        samples.extend(parse_memory_sample(&event, args));
+    }
+
+    println!("Found {} memory samples", samples.len());
+    if samples.is_empty() {
+        return Err(
+            "No memory events found (expected '[memory]' instant events or memory counters)".into(),
+        );
+    }
+
+    // Carry the last known value forward per device so every sample has both series.
+    samples.sort_by(|a, b| a.time_us.partial_cmp(&b.time_us).unwrap());
+    let mut last: BTreeMap<i64, (Option<f64>, Option<f64>)> = BTreeMap::new();
+    for sample in &mut samples {
+        let entry = last.entry(sample.device).or_insert((None, None));
+        entry.0 = sample.allocated_bytes.or(entry.0);
+        entry.1 = sample.reserved_bytes.or(entry.1);
+        sample.allocated_bytes = entry.0;
+        sample.reserved_bytes = entry.1;
+    }
+
+    let steps = config.step_marker.build_steps(marker_events);
+
+    for &device in last.keys() {
+        let device_samples: Vec<&MemorySample> =
+            samples.iter().filter(|s| s.device == device).collect();
+        println!(
+            "\n--- Device {} memory ({} samples) ---",
+            device,
+            device_samples.len()
+        );
+
+        for (label, reserved) in [("allocated", false), ("reserved", true)] {
+            let value = |s: &MemorySample| {
+                if reserved {
+                    s.reserved_bytes
+                } else {
+                    s.allocated_bytes
+                }
+            };
+            let Some(peak) = device_samples
+                .iter()
+                .filter(|s| value(s).is_some())
+                .max_by(|a, b| value(a).partial_cmp(&value(b)).unwrap())
+            else {
+                continue;
+            };
+
+            let step = steps
+                .iter()
+                .find(|step| peak.time_us >= step.start_time && peak.time_us <= step.end_time)
+                .map_or("-", |step| step.name.as_str());
+            // Innermost host span containing the peak.
+            let operator = host_spans
+                .iter()
+                .filter(|span| peak.time_us >= span.start && peak.time_us <= span.end)
+                .min_by(|a, b| (a.end - a.start).partial_cmp(&(b.end - b.start)).unwrap())
+                .map(|span| match &span.tid {
+                    Some(tid) => format!("{} (tid {})", span.name, tid),
+                    None => span.name.clone(),
+                })
+                .unwrap_or_else(|| "-".to_string());
+
+            println!(
+                "Peak {:<9} {:>12.3} MiB at {:.3} us | step: {} | operator: {}",
+                label,
+                value(peak).unwrap_or(0.0) / (1024.0 * 1024.0),
+                peak.time_us,
+                step,
+                operator
+            );
+        }
+    }
+
+    if let Some(ref csv_path) = config.output_csv {
+        write_memory_csv(&samples, csv_path)?;
+    }
+
+    Ok(())
+}
+
+/// Write the memory timeline to a CSV file.
+fn write_memory_csv(samples: &[MemorySample], output_file: &str) -> Result<(), Box<dyn Error>> {
+    println!("\nWriting memory timeline to CSV: {}", output_file);
+    let csv_file = File::create(output_file)?;
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(csv_file));
+
+    for sample in samples {
+        wtr.serialize(sample)?;
+    }
+
+    wtr.flush()?;
+    println!(
+        "Successfully wrote {} records to {}",
+        samples.len(),
+        output_file
+    );
+
+    Ok(())
+}
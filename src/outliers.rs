@@ -0,0 +1,434 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::common::GpuOperation;
+use crate::profile_stats::{ProfileStatsRecord, StepStatus};
+
+/// How far ahead the alignment looks for a matching operation name.
+const ALIGN_WINDOW: usize = 32;
+/// Number of offending positions listed per outlier step.
+const MAX_OFFENSES_PER_STEP: usize = 3;
+
+/// A single contribution to a step's deviation from the reference timeline.
+#[derive(Debug, Clone)]
+pub enum Offense {
+    /// Aligned kernel ran longer than its average.
+    LongerKernel {
+        position: usize,
+        name: String,
+        duration: f64,
+        avg_duration: f64,
+    },
+    /// Gap before an aligned kernel was larger than its average bubble.
+    LargerBubble {
+        position: usize,
+        name: String,
+        bubble: f64,
+        avg_bubble: f64,
+    },
+    /// Kernel not present in the reference sequence.
+    ExtraKernel {
+        index: usize,
+        name: String,
+        duration: f64,
+    },
+}
+
+impl Offense {
+    /// Time (us) this offense adds compared to the reference.
+    fn excess(&self) -> f64 {
+        match self {
+            Offense::LongerKernel {
+                duration,
+                avg_duration,
+                ..
+            } => duration - avg_duration,
+            Offense::LargerBubble {
+                bubble, avg_bubble, ..
+            } => bubble - avg_bubble,
+            Offense::ExtraKernel { duration, .. } => *duration,
+        }
+    }
+}
+
+impl fmt::Display for Offense {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Offense::LongerKernel {
+                position,
+                name,
+                duration,
+                avg_duration,
+            } => write!(
+                f,
+                "+{:.3} us  longer kernel  #{} {} ({:.3} us vs avg {:.3} us)",
+                self.excess(),
+                position,
+                name,
+                duration,
+                avg_duration
+            ),
+            Offense::LargerBubble {
+                position,
+                name,
+                bubble,
+                avg_bubble,
+            } => write!(
+                f,
+                "+{:.3} us  larger bubble  before #{} {} ({:.3} us vs avg {:.3} us)",
+                self.excess(),
+                position,
+                name,
+                bubble,
+                avg_bubble
+            ),
+            Offense::ExtraKernel {
+                index,
+                name,
+                duration,
+            } => write!(
+                f,
+                "+{:.3} us  extra kernel   at index {} {}",
+                duration, index, name
+            ),
+        }
+    }
+}
+
+/// Deviation of one step from the reference timeline.
+#[derive(Debug, Clone)]
+pub struct StepDeviation {
+    pub step_name: String,
+    pub status: StepStatus,
+    /// End of the last operation relative to the step (trim) start, in us.
+    pub span: f64,
+    /// Span minus the reference span, in us.
+    pub deviation: f64,
+    /// Reference positions absent from the step.
+    pub missing_kernels: usize,
+    /// Offenses sorted by excess time, largest first.
+    pub offenses: Vec<Offense>,
+}
+
+/// Align a step's operation names to the reference sequence.
+/// Returns, for each step operation, the matched reference position (`None` for extra operations).
+fn align_to_reference(
+    ops: &[GpuOperation],
+    reference: &[ProfileStatsRecord],
+) -> Vec<Option<usize>> {
+    let mut alignment = vec![None; ops.len()];
+    let (mut i, mut j) = (0, 0);
+
+    while i < ops.len() && j < reference.len() {
+        if ops[i].name == reference[j].operation_name {
+            alignment[i] = Some(j);
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        // Look ahead for the nearest resynchronization point in either sequence.
+        let extra = (1..=ALIGN_WINDOW).find(|&k| {
+            ops.get(i + k)
+                .is_some_and(|op| op.name == reference[j].operation_name)
+        });
+        let missing = (1..=ALIGN_WINDOW).find(|&k| {
+            reference
+                .get(j + k)
+                .is_some_and(|r| r.operation_name == ops[i].name)
+        });
+
+        match (extra, missing) {
+            (Some(e), Some(m)) if m < e => j += m,
+            (Some(e), _) => i += e,
+            (None, Some(m)) => j += m,
+            // Substitution: treat the step operation as extra and the reference one as missing.
+            (None, None) => {
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    alignment
+}
+
+/// Compute a step's deviation from the averaged reference timeline.
+fn step_deviation(
+    step_name: &str,
+    status: StepStatus,
+    ops: &[GpuOperation],
+    reference: &[ProfileStatsRecord],
+) -> StepDeviation {
+    let span = ops.last().map_or(0.0, |op| op.end_time);
+    let reference_span = reference.last().map_or(0.0, |r| r.avg_end_time_us);
+
+    let alignment = if status == StepStatus::Used {
+        (0..ops.len()).map(Some).collect()
+    } else {
+        align_to_reference(ops, reference)
+    };
+
+    let mut offenses: Vec<Offense> = Vec::new();
+    let mut prev_end_time = 0.0;
+    for (idx, (op, aligned)) in ops.iter().zip(&alignment).enumerate() {
+        let bubble = (op.start_time - prev_end_time).max(0.0);
+        prev_end_time = op.end_time;
+
+        let Some(position) = *aligned else {
+            offenses.push(Offense::ExtraKernel {
+                index: idx,
+                name: op.name.clone(),
+                duration: op.duration,
+            });
+            continue;
+        };
+
+        let reference_op = &reference[position];
+        if op.duration > reference_op.avg_duration_us {
+            offenses.push(Offense::LongerKernel {
+                position,
+                name: op.name.clone(),
+                duration: op.duration,
+                avg_duration: reference_op.avg_duration_us,
+            });
+        }
+        if bubble > reference_op.bubble_time_us {
+            offenses.push(Offense::LargerBubble {
+                position,
+                name: op.name.clone(),
+                bubble,
+                avg_bubble: reference_op.bubble_time_us,
+            });
+        }
+    }
+
+    offenses.sort_by(|a, b| {
+        b.excess()
+            .partial_cmp(&a.excess())
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let matched = alignment.iter().filter(|a| a.is_some()).count();
+
+    StepDeviation {
+        step_name: step_name.to_string(),
+        status,
+        span,
+        deviation: span - reference_span,
+        missing_kernels: reference.len().saturating_sub(matched),
+        offenses,
+    }
+}
+
+/// Rank steps by how much longer they are than the reference timeline.
+/// Empty steps are ignored; only steps slower than the reference are returned.
+pub fn find_outlier_steps(
+    step_names: &[&str],
+    statuses: &[StepStatus],
+    step_operations: &[&[GpuOperation]],
+    reference: &[ProfileStatsRecord],
+) -> Vec<StepDeviation> {
+    let mut deviations: Vec<StepDeviation> = step_names
+        .iter()
+        .zip(statuses)
+        .zip(step_operations)
+        .filter(|((_, &status), _)| status != StepStatus::Empty)
+        .map(|((name, &status), ops)| step_deviation(name, status, ops, reference))
+        .filter(|d| d.deviation > 0.0)
+        .collect();
+
+    deviations.sort_by(|a, b| {
+        b.deviation
+            .partial_cmp(&a.deviation)
+            .unwrap_or(Ordering::Equal)
+    });
+    deviations
+}
+
+/// Print the worst steps with their top offending positions.
+pub fn print_outlier_report(
+    deviations: &[StepDeviation],
+    reference: &[ProfileStatsRecord],
+    total_steps: usize,
+    count: usize,
+) {
+    if deviations.is_empty() || count == 0 {
+        return;
+    }
+
+    let reference_span = reference.last().map_or(0.0, |r| r.avg_end_time_us);
+    println!(
+        "\n--- Outlier steps (worst {} of {} steps, reference span {:.3} us) ---",
+        count.min(deviations.len()),
+        total_steps,
+        reference_span
+    );
+
+    for (rank, deviation) in deviations.iter().take(count).enumerate() {
+        let percent = if reference_span > 0.0 {
+            deviation.deviation / reference_span * 100.0
+        } else {
+            0.0
+        };
+        println!(
+            "{}. {} [{}]: span {:.3} us (+{:.3} us, +{:.1}%)",
+            rank + 1,
+            deviation.step_name,
+            deviation.status,
+            deviation.span,
+            deviation.deviation,
+            percent
+        );
+        for offense in deviation.offenses.iter().take(MAX_OFFENSES_PER_STEP) {
+            println!("     {}", offense);
+        }
+        if deviation.missing_kernels > 0 {
+            println!(
+                "     {} reference kernels missing from this step",
+                deviation.missing_kernels
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::GpuOpKind;
+
+    /// Reference of back-to-back 10 us kernels with no bubbles.
+    fn reference(names: &[&str]) -> Vec<ProfileStatsRecord> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| ProfileStatsRecord {
+                operation_name: name.to_string(),
+                avg_start_time_us: idx as f64 * 10.0,
+                avg_end_time_us: idx as f64 * 10.0 + 10.0,
+                avg_duration_us: 10.0,
+                bubble_time_us: 0.0,
+            })
+            .collect()
+    }
+
+    /// Operations laid out back to back, with `(name, duration, bubble before)`.
+    fn step(ops: &[(&str, f64, f64)]) -> Vec<GpuOperation> {
+        let mut end = 0.0;
+        ops.iter()
+            .map(|&(name, duration, bubble)| {
+                let start = end + bubble;
+                end = start + duration;
+                GpuOperation {
+                    name: name.to_string(),
+                    start_time: start,
+                    end_time: end,
+                    duration,
+                    kind: GpuOpKind::Kernel,
+                    device: Some(0),
+                    stream: Some(7),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn inserted_kernel_is_extra() {
+        let reference = reference(&["k0", "k1", "k2", "k3"]);
+        let ops = step(&[
+            ("k0", 10.0, 0.0),
+            ("memcpy", 5.0, 0.0),
+            ("k1", 10.0, 0.0),
+            ("k2", 10.0, 0.0),
+            ("k3", 10.0, 0.0),
+        ]);
+        assert_eq!(
+            align_to_reference(&ops, &reference),
+            [Some(0), None, Some(1), Some(2), Some(3)]
+        );
+
+        let deviation = step_deviation("s", StepStatus::CountMismatch, &ops, &reference);
+        assert_eq!(deviation.deviation, 5.0);
+        assert_eq!(deviation.missing_kernels, 0);
+        assert!(matches!(
+            &deviation.offenses[..],
+            [Offense::ExtraKernel { index: 1, name, .. }] if name == "memcpy"
+        ));
+    }
+
+    #[test]
+    fn dropped_kernel_is_missing() {
+        let reference = reference(&["k0", "k1", "k2", "k3"]);
+        let ops = step(&[("k0", 10.0, 0.0), ("k2", 10.0, 0.0), ("k3", 10.0, 0.0)]);
+        assert_eq!(
+            align_to_reference(&ops, &reference),
+            [Some(0), Some(2), Some(3)]
+        );
+        let deviation = step_deviation("s", StepStatus::CountMismatch, &ops, &reference);
+        assert_eq!(deviation.missing_kernels, 1);
+    }
+
+    #[test]
+    fn longer_kernel_and_larger_bubble_are_attributed() {
+        let reference = reference(&["k0", "k1", "k2", "k3"]);
+        let ops = step(&[
+            ("k0", 10.0, 0.0),
+            ("k1", 30.0, 0.0),
+            ("k2", 10.0, 15.0),
+            ("k3", 10.0, 0.0),
+        ]);
+
+        let deviation = step_deviation("s", StepStatus::Used, &ops, &reference);
+        assert_eq!(deviation.deviation, 35.0);
+        match &deviation.offenses[..] {
+            [Offense::LongerKernel {
+                position: 1,
+                duration,
+                ..
+            }, Offense::LargerBubble {
+                position: 2,
+                bubble,
+                ..
+            }] => {
+                assert_eq!(*duration, 30.0);
+                assert_eq!(*bubble, 15.0);
+            }
+            other => panic!("unexpected offenses: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn extra_kernels_beyond_lookahead_lose_alignment() {
+        let reference = reference(&["k0", "k1", "k2"]);
+        let mut layout = vec![("k0", 10.0, 0.0)];
+        layout.extend(std::iter::repeat_n(("memcpy", 1.0, 0.0), ALIGN_WINDOW + 1));
+        layout.extend([("k1", 10.0, 0.0), ("k2", 10.0, 0.0)]);
+        let ops = step(&layout);
+
+        // The resynchronization point is out of reach, so the step is treated as a
+        // run of substitutions after the first kernel.
+        let alignment = align_to_reference(&ops, &reference);
+        assert_eq!(alignment[0], Some(0));
+        assert!(alignment[1..].iter().all(Option::is_none));
+
+        let deviation = step_deviation("s", StepStatus::CountMismatch, &ops, &reference);
+        assert_eq!(deviation.missing_kernels, 2);
+    }
+
+    #[test]
+    fn only_slower_steps_are_reported_worst_first() {
+        let reference = reference(&["k0", "k1"]);
+        let fast = step(&[("k0", 10.0, 0.0), ("k1", 10.0, 0.0)]);
+        let slow = step(&[("k0", 10.0, 0.0), ("k1", 20.0, 0.0)]);
+        let slower = step(&[("k0", 10.0, 5.0), ("k1", 20.0, 0.0)]);
+
+        let deviations = find_outlier_steps(
+            &["fast", "slow", "slower"],
+            &[StepStatus::Used; 3],
+            &[&fast, &slow, &slower],
+            &reference,
+        );
+        let names: Vec<&str> = deviations.iter().map(|d| d.step_name.as_str()).collect();
+        assert_eq!(names, ["slower", "slow"]);
+    }
+}
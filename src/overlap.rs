@@ -0,0 +1,214 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::common::{GpuOpKind, GpuOperation};
+
+/// Name fragments (lowercase) of collective communication kernels.
+const COMM_KERNEL_PATTERNS: &[&str] = &[
+    "nccl",
+    "allreduce",
+    "all_reduce",
+    "allgather",
+    "all_gather",
+    "reducescatter",
+    "reduce_scatter",
+    "alltoall",
+    "all_to_all",
+    "sendrecv",
+    "broadcast",
+    "cross_device_reduce",
+];
+
+/// Whether a GPU operation is a communication kernel.
+pub fn is_comm_kernel(op: &GpuOperation) -> bool {
+    if op.kind != GpuOpKind::Kernel {
+        return false;
+    }
+    let lower = op.name.to_lowercase();
+    COMM_KERNEL_PATTERNS.iter().any(|p| lower.contains(p))
+}
+
+/// Communication/computation overlap of one step.
+#[derive(Debug, Serialize)]
+pub struct OverlapRecord {
+    pub step_index: usize,
+    pub step_name: String,
+    pub phase: String,
+    pub comm_kernels: usize,
+    /// Total duration of communication kernels.
+    pub comm_time_us: f64,
+    /// Communication time during which a compute kernel ran on the same device.
+    pub overlapped_us: f64,
+    /// Communication time not hidden behind compute.
+    pub exposed_us: f64,
+    pub overlap_ratio: f64,
+}
+
+/// Merge intervals into a sorted, disjoint union.
+fn merge_intervals(mut intervals: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    intervals.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let mut merged: Vec<(f64, f64)> = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Length of `[start, end]` covered by a sorted, disjoint union of intervals.
+fn covered_length(union: &[(f64, f64)], start: f64, end: f64) -> f64 {
+    let first = union.partition_point(|iv| iv.1 <= start);
+    union[first..]
+        .iter()
+        .take_while(|iv| iv.0 < end)
+        .map(|iv| (iv.1.min(end) - iv.0.max(start)).max(0.0))
+        .sum()
+}
+
+/// Compute communication kernel count, total time and overlapped time for one step.
+pub fn step_overlap(ops: &[GpuOperation]) -> (usize, f64, f64) {
+    // Union of compute kernel intervals per device.
+    let mut compute: HashMap<Option<i64>, Vec<(f64, f64)>> = HashMap::new();
+    for op in ops
+        .iter()
+        .filter(|op| op.kind == GpuOpKind::Kernel && !is_comm_kernel(op))
+    {
+        compute
+            .entry(op.device)
+            .or_default()
+            .push((op.start_time, op.end_time));
+    }
+    let compute: HashMap<Option<i64>, Vec<(f64, f64)>> = compute
+        .into_iter()
+        .map(|(device, intervals)| (device, merge_intervals(intervals)))
+        .collect();
+
+    let mut count = 0;
+    let mut comm_time = 0.0;
+    let mut overlapped = 0.0;
+    for op in ops.iter().filter(|op| is_comm_kernel(op)) {
+        count += 1;
+        comm_time += op.duration;
+        if let Some(union) = compute.get(&op.device) {
+            overlapped += covered_length(union, op.start_time, op.end_time);
+        }
+    }
+
+    (count, comm_time, overlapped)
+}
+
+/// Print averaged overlap over the steps of a phase that contain communication.
+pub fn print_overlap_summary(records: &[&OverlapRecord], phase: &str) {
+    let with_comm: Vec<&&OverlapRecord> = records.iter().filter(|r| r.comm_kernels > 0).collect();
+    println!("\n--- Communication overlap ({} steps) ---", phase);
+    if with_comm.is_empty() {
+        println!("No communication kernels found");
+        return;
+    }
+
+    let n = with_comm.len() as f64;
+    let comm = with_comm.iter().map(|r| r.comm_time_us).sum::<f64>() / n;
+    let overlapped = with_comm.iter().map(|r| r.overlapped_us).sum::<f64>() / n;
+    let exposed = with_comm.iter().map(|r| r.exposed_us).sum::<f64>() / n;
+    let kernels = with_comm.iter().map(|r| r.comm_kernels).sum::<usize>() as f64 / n;
+
+    println!(
+        "Steps with communication: {} of {}",
+        with_comm.len(),
+        records.len()
+    );
+    println!("Avg comm kernels:   {:.1}", kernels);
+    println!("Avg comm time:      {:.3} us", comm);
+    println!(
+        "Avg overlapped:     {:.3} us ({:.1}%)",
+        overlapped,
+        if comm > 0.0 {
+            overlapped / comm * 100.0
+        } else {
+            0.0
+        }
+    );
+    println!("Avg exposed:        {:.3} us", exposed);
+}
+
+/// Write per-step overlap records to a CSV file.
+pub fn write_overlap_csv(
+    records: &[OverlapRecord],
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    println!("Writing communication overlap to CSV file: {}", output_file);
+    let csv_file = File::create(output_file)?;
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(csv_file));
+
+    for record in records {
+        wtr.serialize(record)?;
+    }
+
+    wtr.flush()?;
+    println!(
+        "Successfully wrote {} records to {}",
+        records.len(),
+        output_file
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(
+        name: &str,
+        kind: GpuOpKind,
+        device: i64,
+        stream: i64,
+        start: f64,
+        end: f64,
+    ) -> GpuOperation {
+        GpuOperation {
+            name: name.to_string(),
+            start_time: start,
+            end_time: end,
+            duration: end - start,
+            kind,
+            device: Some(device),
+            stream: Some(stream),
+        }
+    }
+
+    #[test]
+    fn union_merges_overlapping_and_touching_intervals() {
+        let merged = merge_intervals(vec![(12.0, 20.0), (0.0, 15.0), (25.0, 40.0), (20.0, 22.0)]);
+        assert_eq!(merged, [(0.0, 22.0), (25.0, 40.0)]);
+        assert_eq!(covered_length(&merged, 10.0, 30.0), 17.0);
+        assert_eq!(covered_length(&merged, 22.0, 25.0), 0.0);
+    }
+
+    #[test]
+    fn partial_overlap_across_streams() {
+        let ops = [
+            op("gemm", GpuOpKind::Kernel, 0, 7, 0.0, 15.0),
+            op("attn", GpuOpKind::Kernel, 0, 7, 12.0, 20.0),
+            op("ncclAllReduce", GpuOpKind::Kernel, 0, 9, 10.0, 30.0),
+            op("MEMCPY_DtoH", GpuOpKind::Memcpy, 0, 8, 20.0, 25.0),
+            op("gemm", GpuOpKind::Kernel, 0, 7, 25.0, 40.0),
+        ];
+        // Compute covers [0, 20] and [25, 40]; the memcpy does not count as compute.
+        assert_eq!(step_overlap(&ops), (1, 20.0, 15.0));
+    }
+
+    #[test]
+    fn compute_on_another_device_does_not_hide_comm() {
+        let ops = [
+            op("gemm", GpuOpKind::Kernel, 0, 7, 0.0, 50.0),
+            op("all_gather_kernel", GpuOpKind::Kernel, 1, 9, 10.0, 30.0),
+        ];
+        assert_eq!(step_overlap(&ops), (1, 20.0, 0.0));
+    }
+}
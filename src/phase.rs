@@ -0,0 +1,287 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Execution phase of a step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    Prefill,
+    Decode,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phase::Prefill => write!(f, "prefill"),
+            Phase::Decode => write!(f, "decode"),
+        }
+    }
+}
+
+/// How steps are split into prefill and decode phases.
+#[derive(Debug, Clone, Copy)]
+pub enum PhaseMode {
+    /// Steps longer than the given duration (ms) are prefill.
+    Threshold(f64),
+    /// Cluster steps by duration and kernel signature.
+    Auto,
+}
+
+/// Minimum separation (in pooled standard deviations of log-duration) between two clusters.
+const MIN_CLUSTER_SEPARATION: f64 = 2.0;
+/// Minimum ratio between the mean durations of two clusters.
+const MIN_CLUSTER_RATIO: f64 = 1.3;
+/// Maximum number of recursive splits when searching for duration clusters.
+const MAX_SPLIT_DEPTH: usize = 3;
+
+/// A kernel is prefill-only if it appears in at least this fraction of long steps...
+const PREFILL_KERNEL_MIN_LONG_FRACTION: f64 = 0.5;
+/// ...and in at most this fraction of decode-length steps.
+const PREFILL_KERNEL_MAX_SHORT_FRACTION: f64 = 0.05;
+/// Name fragments of attention kernels that only run during prefill.
+const PREFILL_KERNEL_HINTS: &[&str] = &["prefill", "varlen", "context_attention"];
+
+/// Find the best split of sorted values into two clusters (2-means on log values).
+/// Returns the index of the first element of the upper cluster if the clusters are well separated.
+fn best_split(sorted: &[f64]) -> Option<usize> {
+    if sorted.len() < 2 || sorted[0] <= 0.0 {
+        return None;
+    }
+
+    let logs: Vec<f64> = sorted.iter().map(|v| v.ln()).collect();
+    let n = logs.len();
+    let mut prefix = vec![0.0; n + 1];
+    let mut prefix_sq = vec![0.0; n + 1];
+    for (i, &x) in logs.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + x;
+        prefix_sq[i + 1] = prefix_sq[i] + x * x;
+    }
+
+    // Sum of squared errors of logs[lo..hi] around its mean.
+    let sse = |lo: usize, hi: usize| {
+        let count = (hi - lo) as f64;
+        let sum = prefix[hi] - prefix[lo];
+        (prefix_sq[hi] - prefix_sq[lo]) - sum * sum / count
+    };
+
+    let (split, total_sse) = (1..n)
+        .map(|k| (k, sse(0, k) + sse(k, n)))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())?;
+
+    let mean_lo = prefix[split] / split as f64;
+    let mean_hi = (prefix[n] - prefix[split]) / (n - split) as f64;
+    let pooled_std = (total_sse / n as f64).sqrt();
+
+    let separated = if pooled_std > 0.0 {
+        (mean_hi - mean_lo) / pooled_std >= MIN_CLUSTER_SEPARATION
+    } else {
+        mean_hi > mean_lo
+    };
+
+    if separated && (mean_hi - mean_lo).exp() >= MIN_CLUSTER_RATIO {
+        Some(split)
+    } else {
+        None
+    }
+}
+
+/// Recursively split sorted values into duration clusters, returned as index ranges.
+fn split_clusters(sorted: &[f64], offset: usize, depth: usize, out: &mut Vec<(usize, usize)>) {
+    match best_split(sorted) {
+        Some(split) if depth < MAX_SPLIT_DEPTH => {
+            split_clusters(&sorted[..split], offset, depth + 1, out);
+            split_clusters(&sorted[split..], offset + split, depth + 1, out);
+        }
+        _ => out.push((offset, offset + sorted.len())),
+    }
+}
+
+/// Find the `[min, max]` duration range of the decode cluster, i.e. the most populous
+/// cluster of step durations. Returns `None` for empty input.
+pub fn decode_duration_range(durations: &[f64]) -> Option<(f64, f64)> {
+    let mut sorted: Vec<f64> = durations.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let mut clusters = Vec::new();
+    split_clusters(&sorted, 0, 0, &mut clusters);
+
+    clusters
+        .iter()
+        .filter(|(lo, hi)| hi > lo)
+        .max_by_key(|(lo, hi)| hi - lo)
+        .map(|&(lo, hi)| (sorted[lo], sorted[hi - 1]))
+}
+
+/// Result of automatic phase classification.
+pub struct PhaseClassification {
+    pub phases: Vec<Phase>,
+    /// Upper bound of the decode duration cluster (same unit as the input durations).
+    pub decode_max_duration: Option<f64>,
+    /// Kernels whose presence marks a step as prefill.
+    pub prefill_kernels: Vec<String>,
+}
+
+/// Classify steps into prefill and decode from their durations and kernel names.
+///
+/// Steps are first clustered by duration; the most populous cluster is decode and anything
+/// longer is a prefill candidate. Kernels that occur in most long steps but almost never in
+/// decode steps (or that match known prefill attention names) form the prefill signature.
+/// When a signature exists it decides the label, so slow decode steps are not mistaken for
+/// prefill; otherwise the duration cluster decides.
+pub fn classify_steps(durations: &[f64], step_kernels: &[HashSet<&str>]) -> PhaseClassification {
+    let decode_max_duration = decode_duration_range(durations).map(|(_, max)| max);
+    let is_long = |dur: f64| decode_max_duration.is_some_and(|max| dur > max);
+
+    let long_count = durations.iter().filter(|&&d| is_long(d)).count();
+    let short_count = durations.len() - long_count;
+
+    // Count in how many long and short steps each kernel appears.
+    let mut presence: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (&dur, kernels) in durations.iter().zip(step_kernels) {
+        for &name in kernels {
+            let entry = presence.entry(name).or_insert((0, 0));
+            if is_long(dur) {
+                entry.0 += 1;
+            } else {
+                entry.1 += 1;
+            }
+        }
+    }
+
+    let mut prefill_kernels: Vec<String> = if long_count > 0 && short_count > 0 {
+        presence
+            .iter()
+            .filter(|(_, &(long, short))| {
+                long as f64 / long_count as f64 >= PREFILL_KERNEL_MIN_LONG_FRACTION
+                    && short as f64 / short_count as f64 <= PREFILL_KERNEL_MAX_SHORT_FRACTION
+            })
+            .map(|(name, _)| name.to_string())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // Without a duration split, fall back to well-known prefill kernel names that only
+    // appear in a minority of steps.
+    if prefill_kernels.is_empty() {
+        prefill_kernels = presence
+            .iter()
+            .filter(|(name, &(long, short))| {
+                let lower = name.to_lowercase();
+                PREFILL_KERNEL_HINTS.iter().any(|hint| lower.contains(hint))
+                    && (long + short) * 2 < durations.len()
+            })
+            .map(|(name, _)| name.to_string())
+            .collect();
+    }
+    prefill_kernels.sort();
+
+    let phases = durations
+        .iter()
+        .zip(step_kernels)
+        .map(|(&dur, kernels)| {
+            let prefill = if prefill_kernels.is_empty() {
+                is_long(dur)
+            } else {
+                prefill_kernels.iter().any(|k| kernels.contains(k.as_str()))
+            };
+            if prefill {
+                Phase::Prefill
+            } else {
+                Phase::Decode
+            }
+        })
+        .collect();
+
+    PhaseClassification {
+        phases,
+        decode_max_duration,
+        prefill_kernels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Twenty ~10 ms decode steps followed by four ~100 ms prefill steps.
+    fn bimodal() -> Vec<f64> {
+        let decode = (0..20).map(|i| 10.0 + (i % 5) as f64 * 0.1);
+        let prefill = (0..4).map(|i| 100.0 + i as f64);
+        decode.chain(prefill).collect()
+    }
+
+    #[test]
+    fn bimodal_durations_split_at_the_gap() {
+        let mut sorted = bimodal();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(best_split(&sorted), Some(20));
+        assert_eq!(decode_duration_range(&bimodal()), Some((10.0, 10.4)));
+
+        let kernels = vec![HashSet::new(); 24];
+        let result = classify_steps(&bimodal(), &kernels);
+        assert_eq!(result.decode_max_duration, Some(10.4));
+        assert!(result.phases[..20].iter().all(|&p| p == Phase::Decode));
+        assert!(result.phases[20..].iter().all(|&p| p == Phase::Prefill));
+    }
+
+    #[test]
+    fn unimodal_durations_stay_one_phase() {
+        let durations: Vec<f64> = (0..30).map(|i| 10.0 + i as f64 * 0.05).collect();
+        assert_eq!(best_split(&durations), None);
+        assert_eq!(decode_duration_range(&durations), Some((10.0, 11.45)));
+
+        let kernels = vec![HashSet::new(); durations.len()];
+        let result = classify_steps(&durations, &kernels);
+        assert!(result.phases.iter().all(|&p| p == Phase::Decode));
+        assert!(result.prefill_kernels.is_empty());
+    }
+
+    #[test]
+    fn non_positive_durations_are_not_split() {
+        assert_eq!(best_split(&[0.0, 10.0, 100.0]), None);
+        assert_eq!(best_split(&[-1.0, 10.0, 100.0]), None);
+        assert_eq!(best_split(&[10.0]), None);
+        assert_eq!(decode_duration_range(&[]), None);
+    }
+
+    #[test]
+    fn signature_kernel_overrides_duration() {
+        // Prefill steps run a kernel decode steps never do; one slow decode step lacks it.
+        let mut durations = bimodal();
+        durations.push(120.0);
+        let kernels: Vec<HashSet<&str>> = (0..durations.len())
+            .map(|i| {
+                let mut set = HashSet::from(["gemm"]);
+                if (20..24).contains(&i) {
+                    set.insert("prefill_attention");
+                }
+                set
+            })
+            .collect();
+
+        let result = classify_steps(&durations, &kernels);
+        assert_eq!(result.prefill_kernels, ["prefill_attention"]);
+        assert!(result.phases[20..24].iter().all(|&p| p == Phase::Prefill));
+        assert_eq!(result.phases[24], Phase::Decode);
+    }
+
+    #[test]
+    fn prefill_name_hint_applies_without_duration_split() {
+        let durations = vec![10.0; 10];
+        let kernels: Vec<HashSet<&str>> = (0..10)
+            .map(|i| {
+                if i == 0 {
+                    HashSet::from(["gemm", "flash_attn_varlen_fwd"])
+                } else {
+                    HashSet::from(["gemm"])
+                }
+            })
+            .collect();
+
+        let result = classify_steps(&durations, &kernels);
+        assert_eq!(result.decode_max_duration, Some(10.0));
+        assert_eq!(result.prefill_kernels, ["flash_attn_varlen_fwd"]);
+        assert_eq!(result.phases[0], Phase::Prefill);
+        assert!(result.phases[1..].iter().all(|&p| p == Phase::Decode));
+    }
+}
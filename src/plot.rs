@@ -0,0 +1,292 @@
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+
+/// Target number of buckets when no bucket width is given.
+const AUTO_BUCKETS: f64 = 20.0;
+/// Upper bound on buckets; narrower widths are widened to stay below it.
+const MAX_BUCKETS: usize = 200;
+/// Width of the longest histogram bar, in characters.
+const BAR_WIDTH: usize = 40;
+/// Size of the terminal CDF chart, in characters.
+const CDF_WIDTH: usize = 60;
+const CDF_HEIGHT: usize = 10;
+/// Size of one SVG panel, in pixels.
+const SVG_WIDTH: f64 = 800.0;
+const SVG_PANEL_HEIGHT: f64 = 280.0;
+const SVG_MARGIN: f64 = 50.0;
+
+/// A set of samples to plot.
+pub struct Distribution {
+    pub label: String,
+    /// Unit of the values, used in axis labels.
+    pub unit: &'static str,
+    pub values: Vec<f64>,
+}
+
+/// Fixed-width buckets over the value range.
+struct Histogram {
+    origin: f64,
+    width: f64,
+    counts: Vec<usize>,
+}
+
+/// Round up to 1, 2 or 5 times a power of ten.
+fn nice_width(raw: f64) -> f64 {
+    let magnitude = 10f64.powf(raw.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|&w| w >= raw)
+        .unwrap_or(10.0 * magnitude)
+}
+
+impl Histogram {
+    fn build(sorted: &[f64], bucket_width: Option<f64>) -> Self {
+        let (min, max) = (sorted[0], sorted[sorted.len() - 1]);
+        let range = max - min;
+        let mut width = match bucket_width {
+            Some(w) => w,
+            None if range > 0.0 => nice_width(range / AUTO_BUCKETS),
+            None => nice_width(min.abs().max(1.0) / 100.0),
+        };
+        if range / width >= MAX_BUCKETS as f64 {
+            let widened = nice_width(range / (MAX_BUCKETS - 1) as f64);
+            println!(
+                "Bucket width {} gives more than {} buckets, using {}",
+                width, MAX_BUCKETS, widened
+            );
+            width = widened;
+        }
+
+        let origin = (min / width).floor() * width;
+        let buckets = ((max - origin) / width).floor() as usize + 1;
+        let mut counts = vec![0; buckets];
+        for &value in sorted {
+            let idx = (((value - origin) / width).floor() as usize).min(buckets - 1);
+            counts[idx] += 1;
+        }
+        Histogram {
+            origin,
+            width,
+            counts,
+        }
+    }
+
+    fn bucket_start(&self, idx: usize) -> f64 {
+        self.origin + idx as f64 * self.width
+    }
+}
+
+/// Fraction of samples at or below `x`.
+fn cdf_at(sorted: &[f64], x: f64) -> f64 {
+    sorted.partition_point(|&v| v <= x) as f64 / sorted.len() as f64
+}
+
+fn sorted_values(dist: &Distribution) -> Vec<f64> {
+    let mut sorted = dist.values.clone();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    sorted
+}
+
+/// Print a text histogram (with cumulative percentages) and a CDF chart.
+pub fn print_distribution(dist: &Distribution, bucket_width: Option<f64>) {
+    if dist.values.is_empty() {
+        return;
+    }
+    let sorted = sorted_values(dist);
+    let hist = Histogram::build(&sorted, bucket_width);
+    let peak = hist.counts.iter().copied().max().unwrap_or(1).max(1);
+    let total = sorted.len() as f64;
+
+    println!(
+        "\n--- {} histogram ({}, bucket {}) ---",
+        dist.label, dist.unit, hist.width
+    );
+    let mut cumulative = 0;
+    for (idx, &count) in hist.counts.iter().enumerate() {
+        cumulative += count;
+        let bar_len = (count * BAR_WIDTH).div_ceil(peak);
+        println!(
+            "[{:>10.3}, {:>10.3}) {:>7} {:<width$} {:>6.1}%",
+            hist.bucket_start(idx),
+            hist.bucket_start(idx + 1),
+            count,
+            "#".repeat(bar_len),
+            cumulative as f64 / total * 100.0,
+            width = BAR_WIDTH
+        );
+    }
+
+    // CDF chart: one column per value slice, one row per 1/CDF_HEIGHT of probability.
+    let (min, max) = (sorted[0], sorted[sorted.len() - 1]);
+    let span = (max - min).max(f64::EPSILON);
+    let levels: Vec<usize> = (0..CDF_WIDTH)
+        .map(|col| {
+            let x = min + span * (col as f64 + 1.0) / CDF_WIDTH as f64;
+            (cdf_at(&sorted, x) * CDF_HEIGHT as f64).round() as usize
+        })
+        .collect();
+    println!("\n--- {} CDF ---", dist.label);
+    for row in (0..=CDF_HEIGHT).rev() {
+        let label = if row == CDF_HEIGHT || row == CDF_HEIGHT / 2 || row == 0 {
+            format!("{:>4.0}%", row as f64 / CDF_HEIGHT as f64 * 100.0)
+        } else {
+            String::new()
+        };
+        let line: String = levels
+            .iter()
+            .map(|&level| if level == row { '*' } else { ' ' })
+            .collect();
+        println!("{:>5} |{}", label, line.trim_end());
+    }
+    println!("      +{}", "-".repeat(CDF_WIDTH));
+    let min_label = format!("{:.3}", min);
+    println!(
+        "       {}{:>width$} {}",
+        min_label,
+        format!("{:.3}", max),
+        dist.unit,
+        width = CDF_WIDTH.saturating_sub(min_label.len())
+    );
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Draw one histogram + CDF panel with its top edge at `top`.
+fn svg_panel(svg: &mut String, dist: &Distribution, bucket_width: Option<f64>, top: f64) {
+    let sorted = sorted_values(dist);
+    let hist = Histogram::build(&sorted, bucket_width);
+    let peak = hist.counts.iter().copied().max().unwrap_or(1).max(1) as f64;
+
+    let left = SVG_MARGIN;
+    let right = SVG_WIDTH - SVG_MARGIN;
+    let plot_top = top + 30.0;
+    let bottom = top + SVG_PANEL_HEIGHT - 30.0;
+    let lo = hist.origin;
+    let hi = hist.bucket_start(hist.counts.len());
+    let x_of = |v: f64| left + (v - lo) / (hi - lo) * (right - left);
+    let y_of = |fraction: f64| bottom - fraction * (bottom - plot_top);
+
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" font-size="14" font-weight="bold">{} ({} values)</text>"#,
+        left,
+        top + 18.0,
+        escape_xml(&dist.label),
+        sorted.len()
+    );
+    for (idx, &count) in hist.counts.iter().enumerate() {
+        let x0 = x_of(hist.bucket_start(idx));
+        let x1 = x_of(hist.bucket_start(idx + 1));
+        let y = y_of(count as f64 / peak);
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="#4c78a8" stroke="white" stroke-width="0.5"/>"##,
+            x0,
+            y,
+            (x1 - x0).max(0.5),
+            bottom - y
+        );
+    }
+
+    // Empirical CDF as a step line, right axis 0-100%.
+    let mut points = format!("{:.2},{:.2}", x_of(lo), y_of(0.0));
+    for (idx, &value) in sorted.iter().enumerate() {
+        let x = x_of(value);
+        let _ = write!(
+            points,
+            " {:.2},{:.2} {:.2},{:.2}",
+            x,
+            y_of(idx as f64 / sorted.len() as f64),
+            x,
+            y_of((idx + 1) as f64 / sorted.len() as f64)
+        );
+    }
+    let _ = write!(points, " {:.2},{:.2}", x_of(hi), y_of(1.0));
+    let _ = writeln!(
+        svg,
+        r##"<polyline points="{}" fill="none" stroke="#e45756" stroke-width="1.5"/>"##,
+        points
+    );
+
+    // Axes and labels.
+    let _ = writeln!(
+        svg,
+        r#"<path d="M{l},{t} L{l},{b} L{r},{b} L{r},{t}" fill="none" stroke="black"/>"#,
+        l = left,
+        t = plot_top,
+        b = bottom,
+        r = right
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" font-size="11">{:.3}</text>"#,
+        left,
+        bottom + 15.0,
+        lo
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" font-size="11" text-anchor="end">{:.3} {}</text>"#,
+        right,
+        bottom + 15.0,
+        hi,
+        dist.unit
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" font-size="11" text-anchor="end">{}</text>"#,
+        left - 5.0,
+        plot_top + 4.0,
+        peak
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" font-size="11">100%</text>"#,
+        right + 5.0,
+        plot_top + 4.0
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" font-size="11">0%</text>"#,
+        right + 5.0,
+        bottom
+    );
+}
+
+/// Write histograms with CDF overlays to an SVG file, one panel per distribution.
+pub fn write_svg(
+    dists: &[Distribution],
+    bucket_width: Option<f64>,
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    let dists: Vec<&Distribution> = dists.iter().filter(|d| !d.values.is_empty()).collect();
+    println!("\nWriting distribution plot to SVG: {}", output_file);
+    let height = SVG_PANEL_HEIGHT * dists.len().max(1) as f64;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif">"#,
+        SVG_WIDTH, height
+    );
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+    for (idx, dist) in dists.iter().enumerate() {
+        svg_panel(&mut svg, dist, bucket_width, idx as f64 * SVG_PANEL_HEIGHT);
+    }
+    svg.push_str("</svg>\n");
+
+    fs::write(output_file, svg)?;
+    println!(
+        "Successfully wrote {} plots to {}",
+        dists.len(),
+        output_file
+    );
+    Ok(())
+}
@@ -1,10 +1,26 @@
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io::BufWriter;
 
-use crate::common::{load_trace_json, parse_time_from_string, TraceEvent};
+use crate::common::{
+    load_trace_json, normalize_op_name, operations_within, GpuOperation, TraceEvent,
+};
+use crate::critical_path::{
+    critical_path, print_average_critical_path, write_critical_path_csv, CriticalPathRecord,
+    PathNode, StreamWait,
+};
+use crate::outliers::{find_outlier_steps, print_outlier_report};
+use crate::overlap::{print_overlap_summary, step_overlap, write_overlap_csv, OverlapRecord};
+use crate::phase::{classify_steps, Phase, PhaseMode};
+use crate::plot::{print_distribution, write_svg, Distribution};
+
+/// Maximum number of operations plotted by `--histogram`.
+const MAX_HISTOGRAM_OPERATIONS: usize = 8;
 
 /// ProfileStep event.
 #[derive(Debug, Clone)]
@@ -14,13 +30,72 @@ pub struct ProfileStep {
     pub end_time: f64,
 }
 
-/// GPU operation record.
+/// How step boundaries are recognized in the trace.
+///
+/// Spec format:
+/// * `cat:<category>` - each complete event of the category is a step (default `cat:ProfileStep`).
+/// * `name:<regex>` - each complete event whose name matches is a step.
+/// * `interval:<regex>` - a step spans from the start of one matching event to the start of the next.
 #[derive(Debug, Clone)]
-pub struct GpuOperation {
-    pub name: String,
-    pub start_time: f64,
-    pub end_time: f64,
-    pub duration: f64,
+pub enum StepMarker {
+    Category(String),
+    Name(Regex),
+    Interval(Regex),
+}
+
+impl Default for StepMarker {
+    fn default() -> Self {
+        StepMarker::Category("ProfileStep".to_string())
+    }
+}
+
+impl StepMarker {
+    pub fn parse(spec: &str) -> Result<Self, Box<dyn Error>> {
+        match spec.split_once(':') {
+            Some(("cat", cat)) => Ok(StepMarker::Category(cat.to_string())),
+            Some(("name", re)) => Ok(StepMarker::Name(Regex::new(re)?)),
+            Some(("interval", re)) => Ok(StepMarker::Interval(Regex::new(re)?)),
+            _ => Err(format!(
+                "Invalid step marker '{}'. Expected cat:<category>, name:<regex> or interval:<regex>",
+                spec
+            )
+            .into()),
+        }
+    }
+
+    pub fn matches(&self, event: &TraceEvent) -> bool {
+        match self {
+            StepMarker::Category(cat) => event.cat.as_deref() == Some(cat.as_str()),
+            StepMarker::Name(re) | StepMarker::Interval(re) => re.is_match(&event.name),
+        }
+    }
+
+    /// Turn matching marker events into steps sorted by start time.
+    pub fn build_steps(&self, mut events: Vec<ProfileStep>) -> Vec<ProfileStep> {
+        events.sort_by(|a, b| a.start_time.partial_cmp(&b.start_time).unwrap());
+        match self {
+            StepMarker::Interval(_) => events
+                .windows(2)
+                .enumerate()
+                .map(|(idx, w)| ProfileStep {
+                    name: format!("{}#{}", w[0].name, idx),
+                    start_time: w[0].start_time,
+                    end_time: w[1].start_time,
+                })
+                .collect(),
+            _ => events,
+        }
+    }
+}
+
+impl fmt::Display for StepMarker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepMarker::Category(cat) => write!(f, "cat:{}", cat),
+            StepMarker::Name(re) => write!(f, "name:{}", re),
+            StepMarker::Interval(re) => write!(f, "interval:{}", re),
+        }
+    }
 }
 
 /// Output statistics record.
@@ -34,44 +109,152 @@ pub struct ProfileStatsRecord {
     pub bubble_time_us: f64,
 }
 
-/// Normalize operation name by stripping the trailing dynamic duration suffix.
-/// e.g. "MEMCPY_DtoH[2.464 us]" -> "MEMCPY_DtoH"
-/// e.g. "kernel_name[123.456 us]" -> "kernel_name"
-fn normalize_op_name(name: &str) -> &str {
-    // Find the last '[' and check if it is a duration suffix.
-    if let Some(bracket_pos) = name.rfind('[') {
-        let suffix = &name[bracket_pos..];
-        // Match "[<number> us]" or "[<number> ms]" format.
-        if suffix.ends_with(" us]") || suffix.ends_with(" ms]") {
-            return &name[..bracket_pos];
+/// Per-step detail record (one row per step and operation position).
+#[derive(Debug, Serialize)]
+pub struct StepDetailRecord {
+    pub step_index: usize,
+    pub step_name: String,
+    pub step_duration_us: f64,
+    pub phase: String,
+    /// Whether the step was used in the averages, or why it was skipped.
+    pub status: String,
+    pub position: Option<usize>,
+    pub kernel_name: Option<String>,
+    pub relative_start_us: Option<f64>,
+    pub relative_end_us: Option<f64>,
+    pub duration_us: Option<f64>,
+    pub bubble_us: Option<f64>,
+}
+
+/// Outcome of a step in the averaged statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepStatus {
+    Used,
+    /// No GPU operations left after trimming.
+    Empty,
+    /// Operation count differs from the reference step.
+    CountMismatch,
+    /// Operation names differ from the reference step.
+    NameMismatch,
+    /// Step belongs to a phase that is not analyzed.
+    PhaseFiltered,
+}
+
+impl fmt::Display for StepStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepStatus::Used => write!(f, "used"),
+            StepStatus::Empty => write!(f, "empty"),
+            StepStatus::CountMismatch => write!(f, "count_mismatch"),
+            StepStatus::NameMismatch => write!(f, "name_mismatch"),
+            StepStatus::PhaseFiltered => write!(f, "phase_filtered"),
         }
     }
-    name
+}
+
+/// Configuration for ProfileStep statistics.
+pub struct ProfileStatsConfig {
+    pub input_file: String,
+    pub output_file: String,
+    /// Optional marker of the first operation counted within each ProfileStep.
+    pub trim_start: Option<KernelMarker>,
+    /// Optional marker of the last operation counted within each ProfileStep.
+    pub trim_end: Option<KernelMarker>,
+    /// How step boundaries are recognized.
+    pub step_marker: StepMarker,
+    /// How ProfileSteps are split into prefill and decode.
+    pub phase_mode: PhaseMode,
+    /// Optional long-format CSV with one row per step and operation.
+    pub detail_csv: Option<String>,
+    /// Number of worst steps explained in the outlier report (0 disables it).
+    pub outlier_count: usize,
+    /// Optional CSV with per-step communication/computation overlap.
+    pub overlap_csv: Option<String>,
+    /// Optional CSV with the per-step critical path across streams.
+    pub critical_path_csv: Option<String>,
+    /// Render duration histograms of the operations matching this regex.
+    pub histogram: Option<Regex>,
+    /// Histogram bucket width in us (automatic if unset).
+    pub bucket_us: Option<f64>,
+    /// Optional SVG output of the histograms.
+    pub svg: Option<String>,
+}
+
+/// Derive the output path for a phase other than decode.
+/// e.g. "profile_stats.csv" -> "profile_stats_prefill.csv"
+fn phase_output_path(output_file: &str, phase: Phase) -> String {
+    match phase {
+        Phase::Decode => output_file.to_string(),
+        _ => match output_file.rfind('.') {
+            Some(dot) if !output_file[dot..].contains('/') => {
+                format!("{}_{}{}", &output_file[..dot], phase, &output_file[dot..])
+            }
+            _ => format!("{}_{}", output_file, phase),
+        },
+    }
+}
+
+/// Durations (us) of every operation whose name matches `pattern`, the operations with
+/// the largest total time first.
+fn operation_distributions(
+    step_operations: &[&[GpuOperation]],
+    pattern: &Regex,
+    phase: Phase,
+) -> Vec<Distribution> {
+    let mut durations: HashMap<&str, Vec<f64>> = HashMap::new();
+    for op in step_operations.iter().flat_map(|ops| ops.iter()) {
+        if pattern.is_match(&op.name) {
+            durations.entry(&op.name).or_default().push(op.duration);
+        }
+    }
+    if durations.is_empty() {
+        println!(
+            "\nNo {} operations match histogram pattern '{}'",
+            phase, pattern
+        );
+        return Vec::new();
+    }
+
+    let mut ranked: Vec<(&str, Vec<f64>)> = durations.into_iter().collect();
+    ranked.sort_by(|a, b| {
+        let total = |v: &[f64]| v.iter().sum::<f64>();
+        total(&b.1).partial_cmp(&total(&a.1)).unwrap()
+    });
+    if ranked.len() > MAX_HISTOGRAM_OPERATIONS {
+        println!(
+            "\n{} operations match histogram pattern '{}', plotting the {} with the most total time",
+            ranked.len(),
+            pattern,
+            MAX_HISTOGRAM_OPERATIONS
+        );
+        ranked.truncate(MAX_HISTOGRAM_OPERATIONS);
+    }
+    ranked
+        .into_iter()
+        .map(|(name, values)| Distribution {
+            label: format!("{} {} duration", phase, name),
+            unit: "us",
+            values,
+        })
+        .collect()
 }
 
 /// Compute average GPU operation statistics within ProfileSteps from a JSON trace file.
 ///
-/// # Arguments
-/// * `input_file` - Path to the input JSON trace file.
-/// * `output_file` - Path to the output CSV statistics file.
-/// * `trim_start_kernel` - Optional kernel name to start counting from within each ProfileStep.
-/// * `decode_max_duration_ms` - Maximum duration threshold (ms); steps exceeding this are treated as prefill and filtered out.
-pub fn analyze_profile_stats(
-    input_file: &str,
-    output_file: &str,
-    trim_start_kernel: Option<&str>,
-    decode_max_duration_ms: f64,
-) -> Result<(), Box<dyn Error>> {
-    println!("Processing JSON file: {}", input_file);
+/// Decode statistics are written to `output_file`. In automatic phase mode, prefill steps
+/// are analyzed separately and written next to it with a `_prefill` suffix.
+pub fn analyze_profile_stats(config: &ProfileStatsConfig) -> Result<(), Box<dyn Error>> {
+    println!("Processing JSON file: {}", config.input_file);
 
-    let json = load_trace_json(input_file)?;
+    let json = load_trace_json(&config.input_file)?;
     let trace_events = json["traceEvents"]
         .as_array()
         .ok_or("traceEvents not found or not an array")?;
 
-    // First pass: collect all ProfileSteps and GPU operations.
-    let mut profile_steps: Vec<ProfileStep> = Vec::new();
+    // First pass: collect all step marker events and GPU operations.
+    let mut marker_events: Vec<ProfileStep> = Vec::new();
     let mut gpu_operations: Vec<GpuOperation> = Vec::new();
+    let mut stream_waits: Vec<StreamWait> = Vec::new();
 
     for event_value in trace_events {
         let event: TraceEvent = match TraceEvent::deserialize(event_value) {
@@ -79,152 +262,481 @@ pub fn analyze_profile_stats(
             Err(_) => continue,
         };
 
-        if let (Some(cat), Some(ph), Some(args)) = (&event.cat, &event.ph, &event.args) {
-            if let (Some(start_str), Some(end_str)) = (&args.start_time, &args.end_time) {
-                if let (Some(start), Some(end)) = (
-                    parse_time_from_string(start_str),
-                    parse_time_from_string(end_str),
-                ) {
-                    if cat == "ProfileStep" && ph == "X" {
-                        profile_steps.push(ProfileStep {
-                            name: event.name.clone(),
-                            start_time: start,
-                            end_time: end,
-                        });
-                    } else if (cat == "Kernel" || cat == "Memcpy" || cat == "Memset") && ph == "X" {
-                        // Normalize name: strip dynamic duration suffix since timing is derived from start/end.
-                        gpu_operations.push(GpuOperation {
-                            name: normalize_op_name(&event.name).to_string(),
-                            start_time: start,
-                            end_time: end,
-                            duration: end - start,
-                        });
-                    }
+        if event.ph.as_deref() != Some("X") {
+            continue;
+        }
+
+        if let Some((start, end)) = event.time_range() {
+            if config.step_marker.matches(&event) {
+                marker_events.push(ProfileStep {
+                    name: event.name.clone(),
+                    start_time: start,
+                    end_time: end,
+                });
+            }
+            // Normalize name: strip dynamic duration suffix since timing is derived from start/end.
+            gpu_operations.extend(GpuOperation::from_event(
+                &event,
+                normalize_op_name(&event.name).to_string(),
+                start,
+                end,
+            ));
+            // Cross-stream waits for the critical path.
+            if event.cat.as_deref() == Some("cuda_sync") {
+                if let Some(wait_on_stream) = event.wait_on_stream() {
+                    stream_waits.push(StreamWait {
+                        device: event.device(),
+                        stream: event.stream(),
+                        wait_on_stream,
+                        time: start,
+                    });
                 }
             }
         }
     }
 
-    println!("Found {} ProfileSteps", profile_steps.len());
+    let profile_steps = config.step_marker.build_steps(marker_events);
+
+    println!(
+        "Found {} steps (marker '{}')",
+        profile_steps.len(),
+        config.step_marker
+    );
     println!("Found {} GPU operations", gpu_operations.len());
 
     if profile_steps.is_empty() {
-        return Err("No ProfileStep events found".into());
+        return Err(format!("No step events found for marker '{}'", config.step_marker).into());
     }
 
     // Sort by start time.
-    profile_steps.sort_by(|a, b| a.start_time.partial_cmp(&b.start_time).unwrap());
     gpu_operations.sort_by(|a, b| a.start_time.partial_cmp(&b.start_time).unwrap());
 
-    // Filter out prefill steps (duration exceeds threshold).
-    // Decode typically takes 10-20ms; prefill takes 40-50ms.
-    let decode_max_duration_us = decode_max_duration_ms * 1000.0; // convert to microseconds
-    let total_before_filter = profile_steps.len();
-    profile_steps.retain(|step| {
-        let duration = step.end_time - step.start_time;
-        duration <= decode_max_duration_us
-    });
+    // For each ProfileStep, collect GPU operations within its time range
+    // and convert to relative timestamps. Operations are sorted, so each step
+    // only visits its own window and the result stays ordered by start time.
+    let step_operations: Vec<Vec<GpuOperation>> = profile_steps
+        .iter()
+        .map(|step| {
+            // Convert to relative time (relative to ProfileStep start).
+            operations_within(&gpu_operations, step.start_time, step.end_time)
+                .map(|op| op.rebased(step.start_time))
+                .collect()
+        })
+        .collect();
 
-    let filtered_count = total_before_filter - profile_steps.len();
-    println!(
-        "Filtered out {} prefill steps (duration > {}ms), {} decode steps remaining",
-        filtered_count,
-        decode_max_duration_ms,
-        profile_steps.len()
-    );
+    let phases = classify_profile_steps(&profile_steps, &step_operations, config.phase_mode);
 
-    if profile_steps.is_empty() {
-        return Err("No decode ProfileStep events found after filtering".into());
-    }
+    let analyzed_phases: &[Phase] = match config.phase_mode {
+        PhaseMode::Threshold(_) => &[Phase::Decode],
+        PhaseMode::Auto => &[Phase::Decode, Phase::Prefill],
+    };
 
-    // For each ProfileStep, collect GPU operations within its time range
-    // and convert to relative timestamps.
-    let mut step_operations: Vec<Vec<GpuOperation>> = Vec::new();
-
-    for step in &profile_steps {
-        let mut ops_in_step: Vec<GpuOperation> = Vec::new();
-
-        for op in &gpu_operations {
-            // GPU operation falls within the ProfileStep time range.
-            if op.start_time >= step.start_time && op.end_time <= step.end_time {
-                // Convert to relative time (relative to ProfileStep start).
-                let relative_start = op.start_time - step.start_time;
-                let relative_end = op.end_time - step.start_time;
-
-                ops_in_step.push(GpuOperation {
-                    name: op.name.clone(),
-                    start_time: relative_start,
-                    end_time: relative_end,
-                    duration: op.duration,
-                });
+    // Only steps in the analyzed phases are reported; the rest are trimmed for the detail CSV.
+    let trimmed_operations: Vec<Vec<GpuOperation>> = profile_steps
+        .iter()
+        .zip(&step_operations)
+        .zip(&phases)
+        .map(|((step, ops_in_step), phase)| {
+            let (trimmed, note) = trim_operations(
+                ops_in_step,
+                config.trim_start.as_ref(),
+                config.trim_end.as_ref(),
+            );
+            if analyzed_phases.contains(phase) {
+                println!(
+                    "ProfileStep '{}': {} GPU operations ({})",
+                    step.name,
+                    trimmed.len(),
+                    note
+                );
             }
+            trimmed
+        })
+        .collect();
+
+    // Steps outside the analyzed phases keep the `PhaseFiltered` status.
+    let mut statuses = vec![StepStatus::PhaseFiltered; profile_steps.len()];
+
+    for &phase in analyzed_phases {
+        let step_indices: Vec<usize> = (0..profile_steps.len())
+            .filter(|&idx| phases[idx] == phase)
+            .collect();
+
+        if step_indices.is_empty() {
+            if phase == Phase::Decode {
+                return Err("No decode ProfileStep events found after filtering".into());
+            }
+            continue;
+        }
+
+        println!("\nAnalyzing {} {} steps", step_indices.len(), phase);
+
+        let phase_operations: Vec<&[GpuOperation]> = step_indices
+            .iter()
+            .map(|&idx| trimmed_operations[idx].as_slice())
+            .collect();
+
+        // Compute per-position averages.
+        // Use the operation name sequence as the alignment reference.
+        let average = calculate_average_stats(&phase_operations)?;
+        for (&idx, &status) in step_indices.iter().zip(&average.statuses) {
+            statuses[idx] = status;
         }
+        let stats = average.records;
+
+        println!(
+            "\nCalculated statistics for {} unique operations",
+            stats.len()
+        );
+
+        // Write to CSV.
+        write_stats_to_csv(&stats, &phase_output_path(&config.output_file, phase))?;
+
+        // Print preview.
+        print_stats_preview(&stats, 10);
 
-        // 按相对开始时间排序
-        ops_in_step.sort_by(|a, b| a.start_time.partial_cmp(&b.start_time).unwrap());
+        if let Some(ref pattern) = config.histogram {
+            let dists = operation_distributions(&phase_operations, pattern, phase);
+            for dist in &dists {
+                print_distribution(dist, config.bucket_us);
+            }
+            if let Some(ref svg_path) = config.svg {
+                write_svg(
+                    &dists,
+                    config.bucket_us,
+                    &phase_output_path(svg_path, phase),
+                )?;
+            }
+        }
 
-        // If a trim kernel is specified, discard operations before it.
-        if let Some(trim_kernel) = trim_start_kernel {
-            if let Some(start_idx) = ops_in_step
+        // Explain the slowest steps relative to the averaged timeline.
+        if config.outlier_count > 0 {
+            let step_names: Vec<&str> = step_indices
                 .iter()
-                .position(|op| op.name.contains(trim_kernel))
-            {
-                // Get the new base timestamp.
-                let new_base_time = ops_in_step[start_idx].start_time;
+                .map(|&idx| profile_steps[idx].name.as_str())
+                .collect();
+            let deviations =
+                find_outlier_steps(&step_names, &average.statuses, &phase_operations, &stats);
+            print_outlier_report(
+                &deviations,
+                &stats,
+                step_indices.len(),
+                config.outlier_count,
+            );
+        }
+    }
 
-                // Trim and recompute relative timestamps.
-                ops_in_step = ops_in_step[start_idx..]
+    if let Some(detail_csv) = &config.detail_csv {
+        write_step_details_csv(
+            &profile_steps,
+            &trimmed_operations,
+            &phases,
+            &statuses,
+            detail_csv,
+        )?;
+    }
+
+    if let Some(critical_path_csv) = &config.critical_path_csv {
+        stream_waits.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+
+        // The critical path covers the untrimmed step, which bounds the step's end time.
+        let paths: Vec<Vec<PathNode>> = profile_steps
+            .iter()
+            .zip(&step_operations)
+            .map(|(step, ops)| {
+                let first = stream_waits.partition_point(|w| w.time < step.start_time);
+                let last = stream_waits.partition_point(|w| w.time <= step.end_time);
+                let waits: Vec<StreamWait> = stream_waits[first..last]
                     .iter()
-                    .map(|op| GpuOperation {
-                        name: op.name.clone(),
-                        start_time: op.start_time - new_base_time,
-                        end_time: op.end_time - new_base_time,
-                        duration: op.duration,
+                    .map(|w| StreamWait {
+                        time: w.time - step.start_time,
+                        ..w.clone()
                     })
                     .collect();
+                critical_path(ops, &waits)
+            })
+            .collect();
 
+        for &phase in analyzed_phases {
+            let phase_paths: Vec<&[PathNode]> = paths
+                .iter()
+                .zip(&phases)
+                .filter(|(_, &p)| p == phase)
+                .map(|(path, _)| path.as_slice())
+                .collect();
+            if !phase_paths.is_empty() {
+                print_average_critical_path(&phase_paths, &phase.to_string());
+            }
+        }
+
+        let records: Vec<CriticalPathRecord> = paths
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, path)| {
+                let step = &profile_steps[idx];
+                let phase = phases[idx];
+                path.iter()
+                    .enumerate()
+                    .map(move |(position, node)| CriticalPathRecord {
+                        step_index: idx,
+                        step_name: step.name.clone(),
+                        phase: phase.to_string(),
+                        position,
+                        kernel_name: node.op.name.clone(),
+                        stream: node.op.stream,
+                        start_us: node.op.start_time,
+                        end_us: node.op.end_time,
+                        duration_us: node.op.duration,
+                        gap_before_us: node.gap_before,
+                        dependency: node.dependency.to_string(),
+                    })
+            })
+            .collect();
+
+        write_critical_path_csv(&records, critical_path_csv)?;
+    }
+
+    if let Some(overlap_csv) = &config.overlap_csv {
+        // Overlap uses the untrimmed step: communication may run outside the trim window.
+        let records: Vec<OverlapRecord> = profile_steps
+            .iter()
+            .zip(&step_operations)
+            .enumerate()
+            .map(|(idx, (step, ops))| {
+                let (comm_kernels, comm_time, overlapped) = step_overlap(ops);
+                OverlapRecord {
+                    step_index: idx,
+                    step_name: step.name.clone(),
+                    phase: phases[idx].to_string(),
+                    comm_kernels,
+                    comm_time_us: comm_time,
+                    overlapped_us: overlapped,
+                    exposed_us: comm_time - overlapped,
+                    overlap_ratio: if comm_time > 0.0 {
+                        overlapped / comm_time
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect();
+
+        for &phase in analyzed_phases {
+            let phase_records: Vec<&OverlapRecord> = records
+                .iter()
+                .zip(&phases)
+                .filter(|(_, &p)| p == phase)
+                .map(|(r, _)| r)
+                .collect();
+            if !phase_records.is_empty() {
+                print_overlap_summary(&phase_records, &phase.to_string());
+            }
+        }
+
+        write_overlap_csv(&records, overlap_csv)?;
+    }
+
+    Ok(())
+}
+
+/// Label each ProfileStep with its phase according to the configured mode.
+fn classify_profile_steps(
+    profile_steps: &[ProfileStep],
+    step_operations: &[Vec<GpuOperation>],
+    phase_mode: PhaseMode,
+) -> Vec<Phase> {
+    let durations: Vec<f64> = profile_steps
+        .iter()
+        .map(|step| step.end_time - step.start_time)
+        .collect();
+
+    match phase_mode {
+        PhaseMode::Threshold(decode_max_duration_ms) => {
+            // Filter out prefill steps (duration exceeds threshold).
+            // Decode typically takes 10-20ms; prefill takes 40-50ms.
+            let decode_max_duration_us = decode_max_duration_ms * 1000.0; // convert to microseconds
+            let phases: Vec<Phase> = durations
+                .iter()
+                .map(|&duration| {
+                    if duration <= decode_max_duration_us {
+                        Phase::Decode
+                    } else {
+                        Phase::Prefill
+                    }
+                })
+                .collect();
+
+            let decode_count = phases.iter().filter(|&&p| p == Phase::Decode).count();
+            println!(
+                "Filtered out {} prefill steps (duration > {}ms), {} decode steps remaining",
+                phases.len() - decode_count,
+                decode_max_duration_ms,
+                decode_count
+            );
+            phases
+        }
+        PhaseMode::Auto => {
+            let step_kernels: Vec<HashSet<&str>> = step_operations
+                .iter()
+                .map(|ops| ops.iter().map(|op| op.name.as_str()).collect())
+                .collect();
+            let classification = classify_steps(&durations, &step_kernels);
+
+            let decode_count = classification
+                .phases
+                .iter()
+                .filter(|&&p| p == Phase::Decode)
+                .count();
+            println!(
+                "Automatic phase classification: {} decode steps, {} prefill steps",
+                decode_count,
+                classification.phases.len() - decode_count
+            );
+            if let Some(max_us) = classification.decode_max_duration {
+                println!("  Decode duration cluster: <= {:.3}ms", max_us / 1000.0);
+            }
+            if !classification.prefill_kernels.is_empty() {
                 println!(
-                    "ProfileStep '{}': {} GPU operations (trimmed from '{}' at index {})",
-                    step.name,
-                    ops_in_step.len(),
-                    trim_kernel,
-                    start_idx
-                );
-            } else {
-                println!(
-                    "ProfileStep '{}': {} GPU operations (trim kernel '{}' not found)",
-                    step.name,
-                    ops_in_step.len(),
-                    trim_kernel
+                    "  Prefill signature kernels: {}",
+                    classification.prefill_kernels.join(", ")
                 );
             }
+            classification.phases
+        }
+    }
+}
+
+/// Kernel name pattern of a trim marker.
+#[derive(Debug, Clone)]
+enum NamePattern {
+    Contains(String),
+    Regex(Regex),
+}
+
+/// Kernel marker delimiting the analyzed window inside each ProfileStep.
+///
+/// Spec format: `[re:]<pattern>[@N]`. The pattern is a substring match, or a regex when
+/// prefixed with `re:`. `@N` selects the Nth matching operation (1-based, default 1);
+/// negative values count from the end, e.g. `@-1` is the last match. A pattern cannot
+/// contain `@`; use `\x40` in a regex instead.
+#[derive(Debug, Clone)]
+pub struct KernelMarker {
+    spec: String,
+    pattern: NamePattern,
+    occurrence: i64,
+}
+
+impl KernelMarker {
+    pub fn parse(spec: &str) -> Result<Self, Box<dyn Error>> {
+        let (pattern_str, occurrence) = match spec.rsplit_once('@') {
+            Some((pattern, n)) => match n.parse::<i64>() {
+                Ok(0) => {
+                    return Err(
+                        format!("Invalid occurrence in marker '{}': must not be 0", spec).into(),
+                    )
+                }
+                Ok(n) => (pattern, n),
+                Err(_) => {
+                    return Err(format!(
+                        "Invalid occurrence in marker '{}': '{}' is not a number \
+                         (write a literal '@' as \\x40 in a re: pattern)",
+                        spec, n
+                    )
+                    .into())
+                }
+            },
+            None => (spec, 1),
+        };
+
+        let pattern = match pattern_str.strip_prefix("re:") {
+            Some(re) => NamePattern::Regex(Regex::new(re)?),
+            None => NamePattern::Contains(pattern_str.to_string()),
+        };
+
+        Ok(Self {
+            spec: spec.to_string(),
+            pattern,
+            occurrence,
+        })
+    }
+
+    fn is_match(&self, name: &str) -> bool {
+        match &self.pattern {
+            NamePattern::Contains(s) => name.contains(s.as_str()),
+            NamePattern::Regex(re) => re.is_match(name),
+        }
+    }
+
+    /// Index of the selected occurrence of the marker in `ops`.
+    fn find(&self, ops: &[GpuOperation]) -> Option<usize> {
+        let mut matches = ops
+            .iter()
+            .enumerate()
+            .filter(|(_, op)| self.is_match(&op.name))
+            .map(|(idx, _)| idx);
+
+        if self.occurrence > 0 {
+            matches.nth(self.occurrence as usize - 1)
         } else {
-            println!(
-                "ProfileStep '{}': {} GPU operations (no trimming)",
-                step.name,
-                ops_in_step.len()
-            );
+            let all: Vec<usize> = matches.collect();
+            all.len()
+                .checked_sub(self.occurrence.unsigned_abs() as usize)
+                .map(|idx| all[idx])
         }
+    }
+}
 
-        step_operations.push(ops_in_step);
+impl fmt::Display for KernelMarker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.spec)
     }
+}
 
-    // Compute per-position averages.
-    // Use the operation name sequence as the alignment reference.
-    let stats = calculate_average_stats(&step_operations)?;
+/// Keep only the operations between the start and end markers (inclusive) and rebase
+/// timestamps on the start marker. Also returns a note describing the trimming.
+fn trim_operations(
+    ops_in_step: &[GpuOperation],
+    start_marker: Option<&KernelMarker>,
+    end_marker: Option<&KernelMarker>,
+) -> (Vec<GpuOperation>, String) {
+    if start_marker.is_none() && end_marker.is_none() {
+        return (ops_in_step.to_vec(), "no trimming".to_string());
+    }
+    let mut notes: Vec<String> = Vec::new();
 
-    println!(
-        "\nCalculated statistics for {} unique operations",
-        stats.len()
-    );
+    // Discard operations before the start marker; timestamps are rebased on it.
+    let (start_idx, new_base_time) = match start_marker.map(|m| (m, m.find(ops_in_step))) {
+        Some((marker, Some(idx))) => {
+            notes.push(format!("from '{}' at index {}", marker, idx));
+            (idx, ops_in_step[idx].start_time)
+        }
+        Some((marker, None)) => {
+            notes.push(format!("start marker '{}' not found", marker));
+            (0, 0.0)
+        }
+        None => (0, 0.0),
+    };
 
-    // Write to CSV.
-    write_stats_to_csv(&stats, output_file)?;
+    // Discard operations after the end marker, searching from the start marker onwards.
+    let end_idx = match end_marker.map(|m| (m, m.find(&ops_in_step[start_idx..]))) {
+        Some((marker, Some(idx))) => {
+            notes.push(format!("to '{}' at index {}", marker, start_idx + idx));
+            start_idx + idx + 1
+        }
+        Some((marker, None)) => {
+            notes.push(format!("end marker '{}' not found", marker));
+            ops_in_step.len()
+        }
+        None => ops_in_step.len(),
+    };
 
-    // Print preview.
-    print_stats_preview(&stats, 10);
+    // Trim and recompute relative timestamps.
+    let trimmed: Vec<GpuOperation> = ops_in_step[start_idx..end_idx]
+        .iter()
+        .map(|op| op.rebased(new_base_time))
+        .collect();
 
-    Ok(())
+    (trimmed, format!("trimmed {}", notes.join(", ")))
 }
 
 /// Accumulated statistics for each reference position.
@@ -248,17 +760,22 @@ impl Default for PositionStats {
     }
 }
 
+/// Averaged statistics together with the per-step outcome.
+struct AverageStats {
+    records: Vec<ProfileStatsRecord>,
+    statuses: Vec<StepStatus>,
+}
+
 /// Compute average statistics across ProfileSteps.
 fn calculate_average_stats(
-    step_operations: &[Vec<GpuOperation>],
-) -> Result<Vec<ProfileStatsRecord>, Box<dyn Error>> {
+    step_operations: &[&[GpuOperation]],
+) -> Result<AverageStats, Box<dyn Error>> {
     if step_operations.is_empty() {
         return Err("No ProfileStep data available".into());
     }
 
     // Select the most common operation count among non-empty steps as the reference sequence.
     // The most frequent count represents the "typical operation sequence".
-    use std::collections::HashMap;
     let mut length_counts: HashMap<usize, usize> = HashMap::new();
     for ops in step_operations.iter().filter(|ops| !ops.is_empty()) {
         *length_counts.entry(ops.len()).or_insert(0) += 1;
@@ -289,12 +806,18 @@ fn calculate_average_stats(
         .collect();
 
     // Iterate over all steps; only process those matching the reference length and names.
+    let mut statuses: Vec<StepStatus> = Vec::with_capacity(num_steps);
     let mut skipped_count = 0;
     let mut name_mismatch_count = 0;
-    for (_step_idx, step_ops) in step_operations.iter().enumerate() {
+    for step_ops in step_operations.iter() {
         // Skip steps with mismatched operation count.
         if step_ops.len() != num_operations {
             skipped_count += 1;
+            statuses.push(if step_ops.is_empty() {
+                StepStatus::Empty
+            } else {
+                StepStatus::CountMismatch
+            });
             continue;
         }
 
@@ -306,9 +829,11 @@ fn calculate_average_stats(
 
         if !names_match {
             name_mismatch_count += 1;
+            statuses.push(StepStatus::NameMismatch);
             continue;
         }
 
+        statuses.push(StepStatus::Used);
         let mut prev_end_time = 0.0; // end time of previous operation
 
         for (idx, cur_op) in step_ops.iter().enumerate() {
@@ -358,7 +883,10 @@ fn calculate_average_stats(
         }
     }
 
-    Ok(stats)
+    Ok(AverageStats {
+        records: stats,
+        statuses,
+    })
 }
 
 /// Write statistics to a CSV file.
@@ -384,6 +912,59 @@ fn write_stats_to_csv(
     Ok(())
 }
 
+/// Write per-step operations in long format, including skipped steps and the skip reason.
+fn write_step_details_csv(
+    profile_steps: &[ProfileStep],
+    step_operations: &[Vec<GpuOperation>],
+    phases: &[Phase],
+    statuses: &[StepStatus],
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    println!("Writing per-step details to CSV file: {}", output_file);
+    let csv_file = File::create(output_file)?;
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(csv_file));
+    let mut rows = 0;
+
+    for (step_idx, step) in profile_steps.iter().enumerate() {
+        let ops = &step_operations[step_idx];
+        let record = |position: Option<usize>, op: Option<&GpuOperation>, bubble: Option<f64>| {
+            StepDetailRecord {
+                step_index: step_idx,
+                step_name: step.name.clone(),
+                step_duration_us: step.end_time - step.start_time,
+                phase: phases[step_idx].to_string(),
+                status: statuses[step_idx].to_string(),
+                position,
+                kernel_name: op.map(|op| op.name.clone()),
+                relative_start_us: op.map(|op| op.start_time),
+                relative_end_us: op.map(|op| op.end_time),
+                duration_us: op.map(|op| op.duration),
+                bubble_us: bubble,
+            }
+        };
+
+        // Keep empty steps visible with a single row without operation fields.
+        if ops.is_empty() {
+            wtr.serialize(record(None, None, None))?;
+            rows += 1;
+            continue;
+        }
+
+        let mut prev_end_time = 0.0;
+        for (position, op) in ops.iter().enumerate() {
+            let bubble = (op.start_time - prev_end_time).max(0.0);
+            wtr.serialize(record(Some(position), Some(op), Some(bubble)))?;
+            prev_end_time = op.end_time;
+            rows += 1;
+        }
+    }
+
+    wtr.flush()?;
+    println!("Successfully wrote {} records to {}", rows, output_file);
+
+    Ok(())
+}
+
 /// Print a preview of statistics.
 fn print_stats_preview(stats: &[ProfileStatsRecord], count: usize) {
     if !stats.is_empty() {
@@ -414,3 +995,60 @@ fn print_stats_preview(stats: &[ProfileStatsRecord], count: usize) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::GpuOpKind;
+
+    fn kernels(names: &[&str]) -> Vec<GpuOperation> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| GpuOperation {
+                name: name.to_string(),
+                start_time: idx as f64 * 10.0,
+                end_time: idx as f64 * 10.0 + 5.0,
+                duration: 5.0,
+                kind: GpuOpKind::Kernel,
+                device: Some(0),
+                stream: Some(7),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn marker_selects_occurrence() {
+        let ops = kernels(&["embedding", "gemm_a", "attn", "gemm_b", "memcpy"]);
+        let find = |spec: &str| KernelMarker::parse(spec).unwrap().find(&ops);
+        assert_eq!(find("gemm"), Some(1));
+        assert_eq!(find("gemm@2"), Some(3));
+        assert_eq!(find("gemm@-1"), Some(3));
+        assert_eq!(find("gemm@3"), None);
+        assert_eq!(find("re:^gemm_b$"), Some(3));
+        assert_eq!(find("re:gemm_a\\x40?"), Some(1));
+    }
+
+    #[test]
+    fn marker_rejects_bad_occurrence() {
+        assert!(KernelMarker::parse("flash_attn@0").is_err());
+        assert!(KernelMarker::parse("flash_attn@first").is_err());
+        assert!(KernelMarker::parse("re:a@b").is_err());
+        assert!(KernelMarker::parse("re:(").is_err());
+    }
+
+    #[test]
+    fn trim_keeps_markers_and_rebases() {
+        let ops = kernels(&["embedding", "gemm_a", "attn", "gemm_b", "memcpy"]);
+        let start = KernelMarker::parse("gemm").unwrap();
+        let end = KernelMarker::parse("gemm@-1").unwrap();
+        let (trimmed, note) = trim_operations(&ops, Some(&start), Some(&end));
+        let names: Vec<&str> = trimmed.iter().map(|op| op.name.as_str()).collect();
+        assert_eq!(names, ["gemm_a", "attn", "gemm_b"]);
+        assert_eq!(trimmed[0].start_time, 0.0);
+        assert_eq!(
+            note,
+            "trimmed from 'gemm' at index 1, to 'gemm@-1' at index 3"
+        );
+    }
+}
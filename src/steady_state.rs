@@ -0,0 +1,186 @@
+/// Minimum segment length between changepoints, in steps...
+const MIN_SEGMENT_STEPS: usize = 5;
+/// ...and as a fraction of the series length.
+const MIN_SEGMENT_FRACTION: f64 = 0.02;
+/// A split is accepted if it reduces the squared error by more than
+/// `PENALTY_FACTOR * sigma^2 * ln(n)` (BIC-style penalty).
+const PENALTY_FACTOR: f64 = 3.0;
+/// Maximum number of changepoints reported.
+const MAX_CHANGEPOINTS: usize = 8;
+/// Values further than this many robust standard deviations from the median are clipped
+/// before detection, so isolated spikes (e.g. prefill steps) do not form segments.
+const WINSOR_SIGMAS: f64 = 5.0;
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+/// Noise level estimated from the median absolute first difference, which is insensitive
+/// to level shifts.
+fn robust_sigma(series: &[f64]) -> f64 {
+    let diffs: Vec<f64> = series.windows(2).map(|w| (w[1] - w[0]).abs()).collect();
+    if diffs.is_empty() {
+        return 0.0;
+    }
+    median(&diffs) / (0.6745 * std::f64::consts::SQRT_2)
+}
+
+/// Clip values to `median ± WINSOR_SIGMAS * MAD`.
+fn winsorize(series: &[f64]) -> Vec<f64> {
+    let center = median(series);
+    let deviations: Vec<f64> = series.iter().map(|x| (x - center).abs()).collect();
+    let spread = median(&deviations) * 1.4826 * WINSOR_SIGMAS;
+    if spread <= 0.0 {
+        return series.to_vec();
+    }
+    series
+        .iter()
+        .map(|x| x.clamp(center - spread, center + spread))
+        .collect()
+}
+
+/// Detect mean-shift changepoints by binary segmentation.
+/// Returns sorted indices where a new segment starts.
+pub fn changepoints(series: &[f64]) -> Vec<usize> {
+    let n = series.len();
+    let min_segment = MIN_SEGMENT_STEPS.max((n as f64 * MIN_SEGMENT_FRACTION) as usize);
+    if n < 2 * min_segment {
+        return Vec::new();
+    }
+
+    let clipped = winsorize(series);
+    let mut prefix = vec![0.0; n + 1];
+    let mut prefix_sq = vec![0.0; n + 1];
+    for (i, &x) in clipped.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + x;
+        prefix_sq[i + 1] = prefix_sq[i] + x * x;
+    }
+    // Sum of squared errors of clipped[lo..hi] around its mean.
+    let sse = |lo: usize, hi: usize| {
+        let count = (hi - lo) as f64;
+        let sum = prefix[hi] - prefix[lo];
+        (prefix_sq[hi] - prefix_sq[lo]) - sum * sum / count
+    };
+
+    let sigma = robust_sigma(&clipped).max(f64::EPSILON);
+    let penalty = PENALTY_FACTOR * sigma * sigma * (n as f64).ln();
+
+    let mut found: Vec<usize> = Vec::new();
+    let mut pending: Vec<(usize, usize)> = vec![(0, n)];
+    while let Some((lo, hi)) = pending.pop() {
+        if found.len() >= MAX_CHANGEPOINTS || hi - lo < 2 * min_segment {
+            continue;
+        }
+        let total = sse(lo, hi);
+        let best = (lo + min_segment..=hi - min_segment)
+            .map(|k| (k, total - sse(lo, k) - sse(k, hi)))
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        if let Some((split, gain)) = best {
+            if gain > penalty {
+                found.push(split);
+                pending.push((lo, split));
+                pending.push((split, hi));
+            }
+        }
+    }
+
+    found.sort_unstable();
+    found
+}
+
+/// The steady-state range `[start, end)` of a latency series: the longest segment between
+/// changepoints (the earliest one on ties).
+pub fn steady_segment(series: &[f64]) -> (usize, usize) {
+    let cuts = changepoints(series);
+    let bounds: Vec<usize> = std::iter::once(0)
+        .chain(cuts)
+        .chain(std::iter::once(series.len()))
+        .collect();
+    bounds
+        .windows(2)
+        .map(|w| (w[0], w[1]))
+        .fold((0, 0), |best, seg| {
+            if seg.1 - seg.0 > best.1 - best.0 {
+                seg
+            } else {
+                best
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic jitter of up to ±0.06 ms.
+    fn noisy(level: f64, i: usize) -> f64 {
+        level + ((i * 7919) % 13) as f64 * 0.01 - 0.06
+    }
+
+    #[test]
+    fn flat_series_has_no_changepoints() {
+        let series: Vec<f64> = (0..200).map(|i| noisy(15.0, i)).collect();
+        assert!(changepoints(&series).is_empty());
+        assert_eq!(steady_segment(&series), (0, 200));
+    }
+
+    #[test]
+    fn isolated_spikes_are_clipped() {
+        let mut series: Vec<f64> = (0..200).map(|i| noisy(15.0, i)).collect();
+        series[50] = 400.0;
+        series[120] = 250.0;
+        assert!(changepoints(&series).is_empty());
+    }
+
+    #[test]
+    fn single_level_shift() {
+        let series: Vec<f64> = (0..200)
+            .map(|i| noisy(if i < 120 { 15.0 } else { 18.0 }, i))
+            .collect();
+        assert_eq!(changepoints(&series), [120]);
+        assert_eq!(steady_segment(&series), (0, 120));
+    }
+
+    #[test]
+    fn warm_up_ramp_is_excluded() {
+        // 20 steps ramping down from 40 ms, then steady at 15 ms.
+        let series: Vec<f64> = (0..200)
+            .map(|i| noisy(15.0 + 25.0 * (1.0 - i as f64 / 20.0).max(0.0), i))
+            .collect();
+        let (lo, hi) = steady_segment(&series);
+        assert!((15..=25).contains(&lo), "steady window starts at {}", lo);
+        assert_eq!(hi, 200);
+    }
+
+    #[test]
+    fn cuts_respect_minimum_segment_length() {
+        let shifted = |at: usize| -> Vec<f64> {
+            (0..200)
+                .map(|i| noisy(if i < at { 30.0 } else { 15.0 }, i))
+                .collect()
+        };
+        assert_eq!(
+            changepoints(&shifted(MIN_SEGMENT_STEPS)),
+            [MIN_SEGMENT_STEPS]
+        );
+        // A shorter leading run cannot form its own segment; the cut lands on the boundary.
+        assert_eq!(
+            changepoints(&shifted(MIN_SEGMENT_STEPS - 1)),
+            [MIN_SEGMENT_STEPS]
+        );
+        assert_eq!(changepoints(&shifted(200 - 2)), [200 - MIN_SEGMENT_STEPS]);
+    }
+
+    #[test]
+    fn short_series_is_not_split() {
+        let series = [10.0, 10.0, 10.0, 30.0, 30.0, 30.0];
+        assert!(changepoints(&series).is_empty());
+    }
+}
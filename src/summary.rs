@@ -0,0 +1,297 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::time::UNIX_EPOCH;
+
+use crate::decode_steps::{DecodeSelection, DecodeStats, DecodeStepsConfig};
+use crate::framework::StepStrategy;
+
+/// Identity of an input file, so results can be traced back to the exact trace.
+#[derive(Debug, Serialize)]
+pub struct InputIdentity {
+    pub path: String,
+    pub size_bytes: u64,
+    pub modified_unix_s: Option<u64>,
+    pub modified_utc: Option<String>,
+    /// 64-bit FNV-1a hash of the file contents, in hex.
+    pub fnv1a64: String,
+}
+
+/// Settings that determined which steps were analyzed.
+#[derive(Debug, Serialize)]
+pub struct SummaryConfig {
+    pub framework: String,
+    pub strategy: StepStrategy,
+    pub marker: String,
+    /// Latency filter bounds applied (ms), after `auto_filter`.
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub auto_filter: bool,
+}
+
+/// Steady-state window the statistics were computed over.
+#[derive(Debug, Serialize)]
+pub struct SummaryWindow {
+    /// Index of the first kept step among all detected steps.
+    pub first_step: usize,
+    pub steps: usize,
+    pub start_time_us: Option<f64>,
+    pub end_time_us: Option<f64>,
+    /// Warm-up rules applied, in order.
+    pub rules: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SummaryCounts {
+    /// Steps detected in the trace.
+    pub detected: usize,
+    /// Steps in the steady-state window (before the latency filter).
+    pub raw: usize,
+    /// Steps kept by the latency filter.
+    pub filtered: usize,
+}
+
+/// Machine-readable result of `decode-steps`.
+#[derive(Debug, Serialize)]
+pub struct DecodeSummary {
+    pub generator: String,
+    pub input: InputIdentity,
+    pub config: SummaryConfig,
+    pub window: SummaryWindow,
+    pub counts: SummaryCounts,
+    pub stats: Option<DecodeStats>,
+}
+
+/// Stream a file through 64-bit FNV-1a.
+fn fnv1a64(path: &str) -> Result<u64, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut buffer = [0u8; 64 * 1024];
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        for &byte in &buffer[..read] {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    Ok(hash)
+}
+
+/// Format seconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`.
+fn format_utc(secs: u64) -> String {
+    // Civil date from day count (Howard Hinnant's algorithm).
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let rem = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+impl InputIdentity {
+    pub fn of(path: &str) -> Result<Self, Box<dyn Error>> {
+        let metadata = fs::metadata(path)?;
+        let modified_unix_s = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+        Ok(Self {
+            path: path.to_string(),
+            size_bytes: metadata.len(),
+            modified_unix_s,
+            modified_utc: modified_unix_s.map(format_utc),
+            fnv1a64: format!("{:016x}", fnv1a64(path)?),
+        })
+    }
+}
+
+impl DecodeSummary {
+    pub fn new(
+        config: &DecodeStepsConfig,
+        selection: &DecodeSelection,
+        stats: Option<DecodeStats>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (min_ms, max_ms) = selection.filter;
+        Ok(Self {
+            generator: format!("trace_processor {}", env!("CARGO_PKG_VERSION")),
+            input: InputIdentity::of(&config.input_file)?,
+            config: SummaryConfig {
+                framework: config.framework.name.clone(),
+                strategy: config.framework.strategy,
+                marker: config.framework.marker.to_string(),
+                min_ms,
+                max_ms,
+                auto_filter: config.auto_filter,
+            },
+            window: SummaryWindow {
+                first_step: selection.window.first,
+                steps: selection.window_steps,
+                start_time_us: selection.window_range.map(|r| r.0),
+                end_time_us: selection.window_range.map(|r| r.1),
+                rules: selection.window.applied.clone(),
+            },
+            counts: SummaryCounts {
+                detected: selection.window.total,
+                raw: selection.window_steps,
+                filtered: selection.steps.len(),
+            },
+            stats,
+        })
+    }
+}
+
+pub fn write_summary_json(
+    summary: &DecodeSummary,
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    println!("\nWriting summary to JSON: {}", output_file);
+    fs::write(output_file, serde_json::to_string_pretty(summary)? + "\n")?;
+    println!("Successfully wrote summary to {}", output_file);
+    Ok(())
+}
+
+pub fn write_summary_yaml(
+    summary: &DecodeSummary,
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    println!("\nWriting summary to YAML: {}", output_file);
+    let mut yaml = yaml_lines(&serde_json::to_value(summary)?).join("\n");
+    yaml.push('\n');
+    fs::write(output_file, yaml)?;
+    println!("Successfully wrote summary to {}", output_file);
+    Ok(())
+}
+
+/// Strings that can be written unquoted without changing type in YAML.
+fn is_plain_yaml(s: &str) -> bool {
+    const RESERVED: &[&str] = &["true", "false", "null", "yes", "no", "on", "off", "~"];
+    s.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'))
+        && !RESERVED.contains(&s.to_lowercase().as_str())
+}
+
+/// A float in YAML 1.1 form: always with a dot, and a signed exponent if any
+/// (`1e20` would otherwise be read as a string).
+fn yaml_float(x: f64) -> String {
+    let text = format!("{:?}", x);
+    let (mantissa, exponent) = match text.split_once('e') {
+        Some((m, e)) => (m.to_string(), Some(e)),
+        None => (text.clone(), None),
+    };
+    let mantissa = if mantissa.contains('.') {
+        mantissa
+    } else {
+        format!("{}.0", mantissa)
+    };
+    match exponent {
+        Some(e) if e.starts_with('-') => format!("{}e{}", mantissa, e),
+        Some(e) => format!("{}e+{}", mantissa, e),
+        None => mantissa,
+    }
+}
+
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::Number(n) if n.is_f64() => n.as_f64().map_or("null".to_string(), yaml_float),
+        Value::String(s) if is_plain_yaml(s) => s.clone(),
+        // JSON string escapes are valid in YAML double-quoted scalars.
+        Value::String(s) => Value::String(s.clone()).to_string(),
+        Value::Object(_) => "{}".to_string(),
+        Value::Array(_) => "[]".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn is_nested(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+/// Block-style YAML for a JSON value, one entry per line, indented two spaces per level.
+fn yaml_lines(value: &Value) -> Vec<String> {
+    let mut lines = Vec::new();
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, item) in map {
+                let key = yaml_scalar(&Value::String(key.clone()));
+                if is_nested(item) {
+                    lines.push(format!("{}:", key));
+                    lines.extend(yaml_lines(item).into_iter().map(|l| format!("  {}", l)));
+                } else {
+                    lines.push(format!("{}: {}", key, yaml_scalar(item)));
+                }
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                let nested = if is_nested(item) {
+                    yaml_lines(item)
+                } else {
+                    vec![yaml_scalar(item)]
+                };
+                for (idx, line) in nested.into_iter().enumerate() {
+                    let prefix = if idx == 0 { "- " } else { "  " };
+                    lines.push(format!("{}{}", prefix, line));
+                }
+            }
+        }
+        other => lines.push(yaml_scalar(other)),
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn floats_always_parse_as_yaml_floats() {
+        assert_eq!(yaml_float(15.0), "15.0");
+        assert_eq!(yaml_float(0.25), "0.25");
+        assert_eq!(yaml_float(1e20), "1.0e+20");
+        assert_eq!(yaml_float(-2.5e-7), "-2.5e-7");
+        assert_eq!(yaml_scalar(&json!(3)), "3");
+    }
+
+    #[test]
+    fn strings_are_quoted_when_ambiguous() {
+        assert_eq!(yaml_scalar(&json!("vllm")), "vllm");
+        assert_eq!(yaml_scalar(&json!("on")), "\"on\"");
+        assert_eq!(yaml_scalar(&json!("12")), "\"12\"");
+        assert_eq!(yaml_scalar(&json!("a: b")), "\"a: b\"");
+    }
+
+    #[test]
+    fn nested_values_are_indented() {
+        let value = json!({"a": {"b": 1.5, "c": []}, "d": ["x", {"e": null}]});
+        assert_eq!(
+            yaml_lines(&value),
+            ["a:", "  b: 1.5", "  c: []", "d:", "  - x", "  - e: null"]
+        );
+    }
+}